@@ -0,0 +1,39 @@
+//! A program that aggregates a chain of training proofs. Each segment proof covers a few epochs of
+//! a private training run and commits the digests of the checkpoints, weights and optimizer state,
+//! it started and ended with. This program verifies every segment with SP1 recursion, checks that
+//! each one resumes from the checkpoint the previous one ended with, and commits only the digests
+//! at both ends of the chain.
+
+// These two lines are necessary for the program to properly compile.
+//
+// Under the hood, we wrap your main function with some extra code so that it behaves properly
+// inside the zkVM.
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use alloy_sol_types::SolType;
+use fibonacci_lib::{AggregatePublicValuesStruct, DigestPublicValuesStruct};
+use sha2::{Digest, Sha256};
+
+pub fn main() {
+    // Read the verifying key of the training program and the public values of each segment.
+    let vkey = sp1_zkvm::io::read::<[u32; 8]>();
+    let public_values = sp1_zkvm::io::read::<Vec<Vec<u8>>>();
+
+    // Verify the segment proofs, which were written to stdin in the same order.
+    for public_values in &public_values {
+        let public_values_digest = Sha256::digest(public_values);
+        sp1_zkvm::lib::verify::verify_sp1_proof(&vkey, &public_values_digest.into());
+    }
+
+    // Check that the segments form one training run.
+    let segments: Vec<DigestPublicValuesStruct> = public_values
+        .iter()
+        .map(|bytes| DigestPublicValuesStruct::abi_decode(bytes, true).expect("invalid segment"))
+        .collect();
+    let aggregate = AggregatePublicValuesStruct::chain(vkey, &segments).unwrap_or_else(|e| panic!("{}", e));
+
+    // Commit to the start and end of the chain.
+    let bytes = AggregatePublicValuesStruct::abi_encode(&aggregate);
+    sp1_zkvm::io::commit_slice(&bytes);
+}
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import {ISP1Verifier} from "@sp1-contracts/ISP1Verifier.sol";
+
+/// @notice The loss and accuracy of the trained weights on the examples with Merkle root
+///         `datasetRoot`, which is the training set or a held-out set. `lossFunction` is 0 for the
+///         squared error, 1 for binary cross-entropy and 2 for softmax cross-entropy. The accuracy
+///         is `correct / total`.
+struct Evaluation {
+    bytes32 datasetRoot;
+    uint32 lossFunction;
+    uint32 loss;
+    uint32 correct;
+    uint32 total;
+}
+
+/// @notice The optimizer of a training run. `kind` is 0 for SGD, 1 for momentum and 2 for Adam,
+///         and `schedule` is 0 for a constant learning rate, 1 for step decay and 2 for cosine
+///         decay. `epoch` is the number of epochs trained before the run, and the digests cover the
+///         training state, loss included, before and after the run.
+struct Optimizer {
+    uint32 kind;
+    uint32 batchSize;
+    uint32 schedule;
+    uint32 scheduleEpochs;
+    uint32 scheduleFactor;
+    uint32 beta1;
+    uint32 beta2;
+    uint32 epsilon;
+    uint32 epoch;
+    bytes32 stateDigest;
+    bytes32 updatedStateDigest;
+}
+
+/// @notice The public values of a perceptron training run, in the order the guest encodes them.
+struct PublicValuesStruct {
+    uint32 d;
+    bytes32 datasetRoot;
+    uint32 t;
+    uint32 wL;
+    uint32 wR;
+    uint32 b;
+    uint32 r;
+    uint32 updatedWL;
+    uint32 updatedWR;
+    uint32 updatedB;
+    Optimizer optimizer;
+    Evaluation evaluation;
+}
+
+/// @title ZkPoTVerifier.
+/// @notice This contract verifies proofs of training and decodes their public values. The weights
+///         and the learning rate are the raw bit patterns of the numeric type the guest trained with.
+contract ZkPoTVerifier {
+    /// @notice The address of the SP1 verifier contract.
+    /// @dev This can either be a specific SP1Verifier for a specific version, or the
+    ///      SP1VerifierGateway which can be used to verify proofs for any version of SP1.
+    ///      For the list of supported verifiers on each chain, see:
+    ///      https://github.com/succinctlabs/sp1-contracts/tree/main/contracts/deployments
+    address public verifier;
+
+    /// @notice The verification key for the training program.
+    bytes32 public trainingProgramVKey;
+
+    /// @notice The proof evaluated the trained weights on another dataset.
+    error WrongEvaluationSet(bytes32 datasetRoot);
+
+    /// @notice The trained weights predicted fewer examples correctly than required.
+    error AccuracyTooLow(uint32 correct, uint32 total);
+
+    constructor(address _verifier, bytes32 _trainingProgramVKey) {
+        verifier = _verifier;
+        trainingProgramVKey = _trainingProgramVKey;
+    }
+
+    /// @notice The entrypoint for verifying the proof of a training run.
+    /// @param _publicValues The encoded public values.
+    /// @param _proofBytes The encoded proof.
+    function verifyTraining(bytes calldata _publicValues, bytes calldata _proofBytes)
+        public
+        view
+        returns (PublicValuesStruct memory)
+    {
+        ISP1Verifier(verifier).verifyProof(trainingProgramVKey, _publicValues, _proofBytes);
+        return abi.decode(_publicValues, (PublicValuesStruct));
+    }
+
+    /// @notice Verify the proof of a training run whose trained weights reached at least
+    ///         `_minPercent` percent accuracy on the examples with Merkle root `_datasetRoot`.
+    /// @param _publicValues The encoded public values.
+    /// @param _proofBytes The encoded proof.
+    /// @param _datasetRoot The Merkle root of the evaluation set.
+    /// @param _minPercent The lowest accepted accuracy, in percent.
+    function verifyAccuracy(
+        bytes calldata _publicValues,
+        bytes calldata _proofBytes,
+        bytes32 _datasetRoot,
+        uint256 _minPercent
+    ) public view returns (PublicValuesStruct memory) {
+        PublicValuesStruct memory values = verifyTraining(_publicValues, _proofBytes);
+        Evaluation memory evaluation = values.evaluation;
+
+        if (evaluation.datasetRoot != _datasetRoot) {
+            revert WrongEvaluationSet(evaluation.datasetRoot);
+        }
+        if (evaluation.total == 0 || 100 * uint256(evaluation.correct) < _minPercent * evaluation.total) {
+            revert AccuracyTooLow(evaluation.correct, evaluation.total);
+        }
+
+        return values;
+    }
+}
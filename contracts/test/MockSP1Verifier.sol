@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import {ISP1Verifier} from "@sp1-contracts/ISP1Verifier.sol";
+
+/// @title MockSP1Verifier.
+/// @notice Stands in for the SP1 verifier gateway so tests run without the real verifiers. It
+///         accepts exactly the (vkey, public values, proof) triples it was told to accept.
+contract MockSP1Verifier is ISP1Verifier {
+    error InvalidProof();
+
+    mapping(bytes32 => bool) public accepted;
+
+    function accept(bytes32 programVKey, bytes calldata publicValues, bytes calldata proofBytes) external {
+        accepted[keccak256(abi.encode(programVKey, publicValues, proofBytes))] = true;
+    }
+
+    function verifyProof(bytes32 programVKey, bytes calldata publicValues, bytes calldata proofBytes)
+        external
+        view
+    {
+        if (!accepted[keccak256(abi.encode(programVKey, publicValues, proofBytes))]) {
+            revert InvalidProof();
+        }
+    }
+}
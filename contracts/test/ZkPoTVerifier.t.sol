@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import {Test} from "forge-std/Test.sol";
+import {stdJson} from "forge-std/StdJson.sol";
+import {ZkPoTVerifier, PublicValuesStruct, Optimizer, Evaluation} from "../src/ZkPoTVerifier.sol";
+import {MockSP1Verifier} from "./MockSP1Verifier.sol";
+
+/// @notice A fixture written by `cargo run -- fixture`.
+struct SP1TrainingProofFixture {
+    PublicValuesStruct values;
+    bytes32 vkey;
+    bytes publicValues;
+    bytes proof;
+}
+
+/// @notice Checks a proof fixture against the contract. The fixture must have been generated with
+///         `cargo run --release -- fixture --system <system>`, otherwise the tests are skipped.
+abstract contract ZkPoTVerifierFixtureTest is Test {
+    using stdJson for string;
+
+    MockSP1Verifier verifier;
+    ZkPoTVerifier zkpot;
+    SP1TrainingProofFixture fixture;
+
+    function fixtureName() internal pure virtual returns (string memory);
+
+    function setUp() public {
+        string memory path = string.concat(vm.projectRoot(), "/src/fixtures/", fixtureName());
+        vm.skip(!vm.exists(path));
+        fixture = loadFixture(vm.readFile(path));
+
+        verifier = new MockSP1Verifier();
+        verifier.accept(fixture.vkey, fixture.publicValues, fixture.proof);
+        zkpot = new ZkPoTVerifier(address(verifier), fixture.vkey);
+    }
+
+    function loadFixture(string memory json) internal pure returns (SP1TrainingProofFixture memory) {
+        PublicValuesStruct memory values;
+        values.d = uint32(json.readUint(".d"));
+        values.datasetRoot = json.readBytes32(".datasetRoot");
+        values.t = uint32(json.readUint(".t"));
+        values.wL = uint32(json.readUint(".wL"));
+        values.wR = uint32(json.readUint(".wR"));
+        values.b = uint32(json.readUint(".b"));
+        values.r = uint32(json.readUint(".r"));
+        values.updatedWL = uint32(json.readUint(".updatedWL"));
+        values.updatedWR = uint32(json.readUint(".updatedWR"));
+        values.updatedB = uint32(json.readUint(".updatedB"));
+        values.optimizer = loadOptimizer(json);
+        values.evaluation = loadEvaluation(json);
+
+        return SP1TrainingProofFixture({
+            values: values,
+            vkey: json.readBytes32(".vkey"),
+            publicValues: json.readBytes(".publicValues"),
+            proof: json.readBytes(".proof")
+        });
+    }
+
+    function loadOptimizer(string memory json) internal pure returns (Optimizer memory optimizer) {
+        optimizer.kind = uint32(json.readUint(".optimizer.kind"));
+        optimizer.batchSize = uint32(json.readUint(".optimizer.batchSize"));
+        optimizer.schedule = uint32(json.readUint(".optimizer.schedule"));
+        optimizer.scheduleEpochs = uint32(json.readUint(".optimizer.scheduleEpochs"));
+        optimizer.scheduleFactor = uint32(json.readUint(".optimizer.scheduleFactor"));
+        optimizer.beta1 = uint32(json.readUint(".optimizer.beta1"));
+        optimizer.beta2 = uint32(json.readUint(".optimizer.beta2"));
+        optimizer.epsilon = uint32(json.readUint(".optimizer.epsilon"));
+        optimizer.epoch = uint32(json.readUint(".optimizer.epoch"));
+        optimizer.stateDigest = json.readBytes32(".optimizer.stateDigest");
+        optimizer.updatedStateDigest = json.readBytes32(".optimizer.updatedStateDigest");
+    }
+
+    function loadEvaluation(string memory json) internal pure returns (Evaluation memory evaluation) {
+        evaluation.datasetRoot = json.readBytes32(".evaluation.datasetRoot");
+        evaluation.lossFunction = uint32(json.readUint(".evaluation.lossFunction"));
+        evaluation.loss = uint32(json.readUint(".evaluation.loss"));
+        evaluation.correct = uint32(json.readUint(".evaluation.correct"));
+        evaluation.total = uint32(json.readUint(".evaluation.total"));
+    }
+
+    function test_ValidTrainingProof() public view {
+        PublicValuesStruct memory values = zkpot.verifyTraining(fixture.publicValues, fixture.proof);
+
+        assertEq(abi.encode(values), abi.encode(fixture.values));
+    }
+
+    function test_RevertWhen_ProofIsInvalid() public {
+        // Fixtures of the mock prover have an empty proof, so the fake proof must not be derived
+        // from its length alone.
+        bytes memory fakeProof = abi.encodePacked(keccak256(fixture.proof));
+
+        vm.expectRevert(MockSP1Verifier.InvalidProof.selector);
+        zkpot.verifyTraining(fixture.publicValues, fakeProof);
+    }
+
+    function test_RevertWhen_PublicValuesAreTampered() public {
+        PublicValuesStruct memory values = fixture.values;
+        values.updatedWL ^= 1;
+
+        vm.expectRevert(MockSP1Verifier.InvalidProof.selector);
+        zkpot.verifyTraining(abi.encode(values), fixture.proof);
+    }
+
+    function test_RevertWhen_ProgramIsDifferent() public {
+        ZkPoTVerifier other = new ZkPoTVerifier(address(verifier), ~fixture.vkey);
+
+        vm.expectRevert(MockSP1Verifier.InvalidProof.selector);
+        other.verifyTraining(fixture.publicValues, fixture.proof);
+    }
+}
+
+contract ZkPoTVerifierGroth16Test is ZkPoTVerifierFixtureTest {
+    function fixtureName() internal pure override returns (string memory) {
+        return "groth16-fixture.json";
+    }
+}
+
+contract ZkPoTVerifierPlonkTest is ZkPoTVerifierFixtureTest {
+    function fixtureName() internal pure override returns (string memory) {
+        return "plonk-fixture.json";
+    }
+}
+
+/// @notice Checks the decoding of public values without a generated fixture.
+contract ZkPoTVerifierTest is Test {
+    bytes32 constant VKEY = bytes32(uint256(1));
+
+    MockSP1Verifier verifier;
+    ZkPoTVerifier zkpot;
+
+    function setUp() public {
+        verifier = new MockSP1Verifier();
+        zkpot = new ZkPoTVerifier(address(verifier), VKEY);
+    }
+
+    function publicValues(uint32 correct, uint32 total) internal pure returns (PublicValuesStruct memory) {
+        // Per-example SGD with a constant learning rate, from the first epoch.
+        Optimizer memory optimizer;
+        optimizer.batchSize = 1;
+        optimizer.stateDigest = keccak256("state");
+        optimizer.updatedStateDigest = keccak256("updated state");
+
+        // 0.1 and -0.1 as `f32` bit patterns.
+        return PublicValuesStruct({
+            d: 1,
+            datasetRoot: keccak256("dataset"),
+            t: 10,
+            wL: 0x3dcccccd,
+            wR: 0xbdcccccd,
+            b: 0,
+            r: 0x3dcccccd,
+            updatedWL: 0x3e4ccccd,
+            updatedWR: 0x3dcccccd,
+            updatedB: 0xbdcccccd,
+            optimizer: optimizer,
+            evaluation: Evaluation({
+                datasetRoot: keccak256("held-out"),
+                lossFunction: 0,
+                loss: 0x3e800000,
+                correct: correct,
+                total: total
+            })
+        });
+    }
+
+    function test_DecodesPublicValues() public {
+        PublicValuesStruct memory expected = publicValues(19, 20);
+        bytes memory encoded = abi.encode(expected);
+        verifier.accept(VKEY, encoded, hex"01");
+
+        PublicValuesStruct memory values = zkpot.verifyTraining(encoded, hex"01");
+
+        assertEq(abi.encode(values), encoded);
+        assertEq(values.updatedWL, expected.updatedWL);
+        assertEq(values.optimizer.batchSize, 1);
+        assertEq(values.evaluation.correct, 19);
+    }
+
+    function test_VerifiesAccuracy() public {
+        bytes memory encoded = abi.encode(publicValues(19, 20));
+        verifier.accept(VKEY, encoded, hex"01");
+
+        PublicValuesStruct memory values = zkpot.verifyAccuracy(encoded, hex"01", keccak256("held-out"), 95);
+
+        assertEq(values.evaluation.total, 20);
+    }
+
+    function test_RevertWhen_AccuracyIsTooLow() public {
+        bytes memory encoded = abi.encode(publicValues(18, 20));
+        verifier.accept(VKEY, encoded, hex"01");
+
+        vm.expectRevert(abi.encodeWithSelector(ZkPoTVerifier.AccuracyTooLow.selector, 18, 20));
+        zkpot.verifyAccuracy(encoded, hex"01", keccak256("held-out"), 95);
+    }
+
+    function test_RevertWhen_EvaluationSetIsDifferent() public {
+        bytes memory encoded = abi.encode(publicValues(19, 20));
+        verifier.accept(VKEY, encoded, hex"01");
+
+        vm.expectRevert(abi.encodeWithSelector(ZkPoTVerifier.WrongEvaluationSet.selector, keccak256("held-out")));
+        zkpot.verifyAccuracy(encoded, hex"01", keccak256("dataset"), 95);
+    }
+
+    function test_RevertWhen_NotAccepted() public {
+        vm.expectRevert(MockSP1Verifier.InvalidProof.selector);
+        zkpot.verifyTraining(new bytes(256), hex"01");
+    }
+}
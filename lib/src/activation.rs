@@ -0,0 +1,318 @@
+//! Activation functions and their derivatives.
+//!
+//! Every function is built from the operations of [`Scalar`] only, so it gives the same bits on
+//! the host and in the guest for each numeric backend. The sigmoid has no exact fixed-point form
+//! and is replaced by the piecewise linear PLAN approximation, whose breakpoints and slopes are
+//! short binary fractions that 16.16 fixed point represents exactly. It stays within 0.02 of the
+//! true sigmoid.
+//!
+//! At a kink, the derivative takes the value of one of the adjacent pieces.
+//!
+//! Away from the kinks, each derivative agrees with a central finite difference:
+//!
+//! ```
+//! use fibonacci_lib::activation::{Activation, Identity, LeakyRelu, Relu, Sigmoid, Step};
+//!
+//! fn check(f: impl Activation) {
+//!     let h = 1e-3f32;
+//!     for x in [-6.0f32, -3.0, -1.5, -0.5, 0.5, 1.5, 3.0, 6.0] {
+//!         let finite_difference = (f.forward(x + h) - f.forward(x - h)) / (2.0 * h);
+//!         assert!((finite_difference - f.derivative(x)).abs() < 1e-2, "at {}", x);
+//!     }
+//! }
+//!
+//! check(Identity);
+//! check(Step);
+//! check(Relu);
+//! check(LeakyRelu);
+//! check(Sigmoid);
+//! ```
+
+use crate::Scalar;
+
+/// An elementwise nonlinearity.
+pub trait Activation {
+    fn forward<T: Scalar>(&self, x: T) -> T;
+
+    fn derivative<T: Scalar>(&self, x: T) -> T;
+}
+
+/// `x`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Identity;
+
+/// The Heaviside step: one for positive inputs, zero otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step;
+
+/// `max(x, 0)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Relu;
+
+/// `x` for positive inputs, `LeakyRelu::SLOPE * x` otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LeakyRelu;
+
+/// The PLAN approximation of `1 / (1 + e^-x)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sigmoid;
+
+impl LeakyRelu {
+    pub const SLOPE: f32 = 0.01;
+}
+
+impl Activation for Identity {
+    fn forward<T: Scalar>(&self, x: T) -> T {
+        x
+    }
+
+    fn derivative<T: Scalar>(&self, _x: T) -> T {
+        T::ONE
+    }
+}
+
+impl Activation for Step {
+    fn forward<T: Scalar>(&self, x: T) -> T {
+        if T::ZERO < x {
+            T::ONE
+        } else {
+            T::ZERO
+        }
+    }
+
+    fn derivative<T: Scalar>(&self, _x: T) -> T {
+        T::ZERO
+    }
+}
+
+impl Activation for Relu {
+    fn forward<T: Scalar>(&self, x: T) -> T {
+        if T::ZERO < x {
+            x
+        } else {
+            T::ZERO
+        }
+    }
+
+    fn derivative<T: Scalar>(&self, x: T) -> T {
+        Step.forward(x)
+    }
+}
+
+impl Activation for LeakyRelu {
+    fn forward<T: Scalar>(&self, x: T) -> T {
+        if T::ZERO < x {
+            x
+        } else {
+            T::from_f32(Self::SLOPE) * x
+        }
+    }
+
+    fn derivative<T: Scalar>(&self, x: T) -> T {
+        if T::ZERO < x {
+            T::ONE
+        } else {
+            T::from_f32(Self::SLOPE)
+        }
+    }
+}
+
+impl Sigmoid {
+    // The pieces of PLAN for `|x|`, as `(start, slope, intercept)`.
+    const PIECES: [(f32, f32, f32); 4] =
+        [(5.0, 0.0, 1.0), (2.375, 0.03125, 0.84375), (1.0, 0.125, 0.625), (0.0, 0.25, 0.5)];
+
+    // The slope and intercept of the piece holding `|x|`.
+    fn piece<T: Scalar>(abs: T) -> (T, T) {
+        let (_, slope, intercept) = Self::PIECES
+            .into_iter()
+            .find(|&(start, _, _)| T::from_f32(start) <= abs)
+            .expect("the last piece starts at zero");
+
+        (T::from_f32(slope), T::from_f32(intercept))
+    }
+}
+
+impl Activation for Sigmoid {
+    fn forward<T: Scalar>(&self, x: T) -> T {
+        // PLAN is defined on `|x|` and mirrored with `sigmoid(-x) = 1 - sigmoid(x)`.
+        let negative = x < T::ZERO;
+        let abs = if negative { -x } else { x };
+        let (slope, intercept) = Self::piece(abs);
+        let y = slope * abs + intercept;
+
+        if negative {
+            T::ONE - y
+        } else {
+            y
+        }
+    }
+
+    fn derivative<T: Scalar>(&self, x: T) -> T {
+        let abs = if x < T::ZERO { -x } else { x };
+        Self::piece(abs).0
+    }
+}
+
+/// The activation of a layer, encoded as a `u32` in layer specs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActivationKind {
+    Identity,
+    Relu,
+    Step,
+    LeakyRelu,
+    Sigmoid,
+}
+
+impl ActivationKind {
+    pub fn to_u32(self) -> u32 {
+        match self {
+            ActivationKind::Identity => 0,
+            ActivationKind::Relu => 1,
+            ActivationKind::Step => 2,
+            ActivationKind::LeakyRelu => 3,
+            ActivationKind::Sigmoid => 4,
+        }
+    }
+
+    pub fn from_u32(i: u32) -> Option<Self> {
+        match i {
+            0 => Some(ActivationKind::Identity),
+            1 => Some(ActivationKind::Relu),
+            2 => Some(ActivationKind::Step),
+            3 => Some(ActivationKind::LeakyRelu),
+            4 => Some(ActivationKind::Sigmoid),
+            _ => None,
+        }
+    }
+}
+
+impl Activation for ActivationKind {
+    fn forward<T: Scalar>(&self, x: T) -> T {
+        match self {
+            ActivationKind::Identity => Identity.forward(x),
+            ActivationKind::Relu => Relu.forward(x),
+            ActivationKind::Step => Step.forward(x),
+            ActivationKind::LeakyRelu => LeakyRelu.forward(x),
+            ActivationKind::Sigmoid => Sigmoid.forward(x),
+        }
+    }
+
+    fn derivative<T: Scalar>(&self, x: T) -> T {
+        match self {
+            ActivationKind::Identity => Identity.derivative(x),
+            ActivationKind::Relu => Relu.derivative(x),
+            ActivationKind::Step => Step.derivative(x),
+            ActivationKind::LeakyRelu => LeakyRelu.derivative(x),
+            ActivationKind::Sigmoid => Sigmoid.derivative(x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::numeric::Q16;
+    use crate::{evaluate_perceptron_on, perceptron_forward, train_perceptron, update_perceptron, DatasetId, Fixed};
+
+    const ALL: [ActivationKind; 5] = [
+        ActivationKind::Identity,
+        ActivationKind::Relu,
+        ActivationKind::Step,
+        ActivationKind::LeakyRelu,
+        ActivationKind::Sigmoid,
+    ];
+
+    // Points at least 1/8 away from every kink, including the breakpoints of the sigmoid.
+    const SMOOTH: [f32; 12] = [-6.0, -4.0, -3.0, -2.0, -1.5, -0.5, 0.5, 1.5, 2.0, 3.0, 4.0, 6.0];
+
+    // The kinks of every activation.
+    const KINKS: [f32; 7] = [-5.0, -2.375, -1.0, 0.0, 1.0, 2.375, 5.0];
+
+    // The central finite difference of `f` at `x` with step `h`, in `T`.
+    fn finite_difference<T: Scalar>(f: ActivationKind, x: T, h: T) -> T {
+        (f.forward(x + h) - f.forward(x - h)) / (h + h)
+    }
+
+    fn check_derivatives<T: Scalar>(h: f32, tolerance: f32) {
+        for f in ALL {
+            for x in SMOOTH {
+                let (x, h) = (T::from_f32(x), T::from_f32(h));
+                let error = (finite_difference(f, x, h) - f.derivative(x)).to_f32().abs();
+                assert!(error <= tolerance, "{:?} at {}: error {}", f, x.to_f32(), error);
+            }
+        }
+    }
+
+    #[test]
+    fn derivatives_match_finite_differences_f32() {
+        check_derivatives::<f32>(1e-3, 1e-3);
+    }
+
+    #[test]
+    fn derivatives_match_finite_differences_q16() {
+        // Every piece is linear, so the only error is the rounding of the leaky slope times `x`.
+        check_derivatives::<Q16>(1.0 / 64.0, 1.0 / 512.0);
+    }
+
+    #[test]
+    fn derivative_at_a_kink_is_one_of_the_adjacent_slopes() {
+        let h = 1.0 / 64.0;
+        for f in ALL {
+            for x in KINKS {
+                let (left, right) = ((f.forward(x) - f.forward(x - h)) / h, (f.forward(x + h) - f.forward(x)) / h);
+                let derivative = f.derivative(x);
+                let close = |slope: f32| (slope - derivative).abs() < 1e-4;
+                assert!(close(left) || close(right), "{:?} at {}: {} not in {{{}, {}}}", f, x, derivative, left, right);
+            }
+        }
+    }
+
+    #[test]
+    fn sigmoid_approximation_is_close() {
+        for i in -800..=800 {
+            let x = i as f32 / 64.0;
+            let exact = 1.0 / (1.0 + (-x).exp());
+            for approximation in [Sigmoid.forward(x), Sigmoid.forward(Q16::from_f32(x)).to_f32()] {
+                assert!((approximation - exact).abs() < 0.02, "at {}: {} for {}", x, approximation, exact);
+            }
+        }
+    }
+
+    #[test]
+    fn q16_matches_f32() {
+        // The breakpoints and slopes are exact in 16.16, so only the rounding of `x` differs.
+        for f in ALL {
+            for i in -800..=800 {
+                let x = i as f32 / 64.0;
+                let (exact, fixed) = (f.forward(x), f.forward(Q16::from_f32(x)).to_f32());
+                assert!((exact - fixed).abs() < 1e-4, "{:?} at {}: {} for {}", f, x, fixed, exact);
+            }
+        }
+    }
+
+    fn check_perceptron<T: Scalar>(d: DatasetId) {
+        let decode = |raw: u32| T::from_f32(Fixed::from_raw(raw).to_f32());
+        let rows: Vec<[T; 3]> = d.rows().iter().map(|row| core::array::from_fn(|i| decode(row[i]))).collect();
+        let (w_l, w_r, b) = train_perceptron(&rows, 20, T::from_f32(0.1), T::ZERO, T::ZERO, T::ZERO);
+
+        for [left, right, truth] in rows {
+            let predicted = perceptron_forward(w_l, w_r, b, left, right);
+            assert!(predicted == truth, "{} at ({}, {})", d, left.to_f32(), right.to_f32());
+        }
+    }
+
+    #[test]
+    fn perceptron_learns_linearly_separable_gates() {
+        for d in [DatasetId::And, DatasetId::Or, DatasetId::Nand, DatasetId::Nor] {
+            check_perceptron::<f32>(d);
+            check_perceptron::<Q16>(d);
+
+            let zero = Fixed::ZERO.to_raw();
+            let (w_l, w_r, b) = update_perceptron(d, 20, Fixed::from_f32(0.1).to_raw(), zero, zero, zero);
+            let metrics = evaluate_perceptron_on(&d.rows(), w_l, w_r, b);
+            assert_eq!(metrics.correct, metrics.total, "{}", d);
+        }
+    }
+}
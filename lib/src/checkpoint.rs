@@ -0,0 +1,469 @@
+//! Versioned model checkpoints, shared by the host and the guest.
+//!
+//! A [`Checkpoint`] holds a header (format version, model, numeric type and architecture), the raw
+//! parameters, the encoded [`TrainingState`] to resume training with, and a SHA-256 digest of all
+//! three. The host saves checkpoints to disk and writes them to `SP1Stdin` as a single byte vector,
+//! which the guest reads back with one `read_vec`.
+//!
+//! The binary encoding is the magic `ZKPC` followed by little-endian `u32` words and the digest:
+//!
+//! ```text
+//! magic | version | model | numeric type | len(a) | a... | len(p) | p... | len(o) | o... | digest
+//! ```
+//!
+//! where `a` is the architecture (layer specs, MLP shape, or nothing for the perceptron), `p` the
+//! parameters and `o` the training state. The digest is `SHA-256(tag || 3 || version || model ||
+//! numeric type || len(a) || a || len(p) || p || len(o) || o)`, hashed like the digests of the
+//! `commitment` module. Private training runs commit this digest, so a chain of runs can only
+//! resume from the exact weights and optimizer state the previous run ended with.
+//!
+//! Decoding checks the magic, the version, the digest, that the number of parameters fits the
+//! architecture, and that the training state fits the parameters. Checkpoints deserialized with
+//! serde are checked the same way.
+//!
+//! ```
+//! use fibonacci_lib::{Checkpoint, MLP};
+//!
+//! let mlp = MLP::fill(4, 2, 3, 7);
+//! let bytes = Checkpoint::from_mlp(&mlp).to_bytes();
+//! assert_eq!(Checkpoint::from_bytes(&bytes).unwrap().to_mlp().unwrap(), mlp);
+//!
+//! let mut tampered = bytes.clone();
+//! tampered[40] ^= 1;
+//! assert!(Checkpoint::from_bytes(&tampered).is_err());
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::commitment::tagged_digest;
+use crate::{Fixed, GuestMode, LayerSpec, NumericType, TrainingState, MLP};
+
+const MAGIC: [u8; 4] = *b"ZKPC";
+const CHECKPOINT_TAG: &[u8] = b"zkpot/checkpoint/v3";
+
+/// What a checkpoint holds, apart from the parameters.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointHeader {
+    pub version: u32,
+    /// The guest mode that trains the model.
+    pub model: GuestMode,
+    /// The numeric type of the raw parameters.
+    pub numeric: NumericType,
+    pub architecture: Vec<u32>,
+}
+
+/// A model checkpoint whose digest has been checked.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedCheckpoint")]
+pub struct Checkpoint {
+    header: CheckpointHeader,
+    params: Vec<u32>,
+    training: Vec<u32>,
+    digest: [u8; 32],
+}
+
+// A checkpoint as deserialized, before its digest is checked.
+#[derive(Deserialize)]
+struct UncheckedCheckpoint {
+    header: CheckpointHeader,
+    params: Vec<u32>,
+    training: Vec<u32>,
+    digest: [u8; 32],
+}
+
+/// Why a checkpoint could not be decoded or used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckpointError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    TrailingBytes,
+    UnknownModel(u32),
+    UnknownNumericType(u32),
+    DigestMismatch,
+    /// The parameters do not fit the architecture.
+    InvalidArchitecture,
+    /// The training state is malformed or does not fit the parameters.
+    InvalidTrainingState,
+    WrongModel { expected: GuestMode, found: GuestMode },
+    WrongNumericType { expected: NumericType, found: NumericType },
+}
+
+impl Checkpoint {
+    /// The version written by this library, and the only one it reads.
+    pub const VERSION: u32 = 3;
+
+    /// A checkpoint of raw parameters of the lib's numeric type, to be trained with per-example
+    /// SGD on the squared error. Panics if the parameters do not fit the architecture.
+    pub fn new(model: GuestMode, architecture: Vec<u32>, params: Vec<u32>) -> Self {
+        let header = CheckpointHeader { version: Self::VERSION, model, numeric: NumericType::FIXED, architecture };
+        assert_eq!(param_count(&header), Some(params.len()), "parameters do not fit the architecture");
+        let training = TrainingState::<Fixed>::sgd().encode();
+        let digest = digest(&header, &params, &training);
+
+        Self { header, params, training, digest }
+    }
+
+    /// The same checkpoint, to be trained with `training` instead. Panics if the optimizer state
+    /// does not fit the parameters.
+    pub fn with_training(mut self, training: &TrainingState<Fixed>) -> Self {
+        self.training = training.encode();
+        assert!(
+            TrainingState::<Fixed>::decode(&self.training, self.params.len()).is_some(),
+            "training state does not fit the parameters"
+        );
+        self.digest = digest(&self.header, &self.params, &self.training);
+
+        self
+    }
+
+    pub fn perceptron(w_l: u32, w_r: u32, b: u32) -> Self {
+        Self::new(GuestMode::Perceptron, Vec::new(), alloc::vec![w_l, w_r, b])
+    }
+
+    pub fn from_mlp(mlp: &MLP) -> Self {
+        Self::new(GuestMode::Mlp, mlp.shape().to_vec(), mlp.params())
+    }
+
+    pub fn header(&self) -> &CheckpointHeader {
+        &self.header
+    }
+
+    pub fn model(&self) -> GuestMode {
+        self.header.model
+    }
+
+    pub fn architecture(&self) -> &[u32] {
+        &self.header.architecture
+    }
+
+    pub fn params(&self) -> &[u32] {
+        &self.params
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        self.digest
+    }
+
+    /// The loss, optimizer, schedule, batch size and epoch count to resume training with.
+    pub fn training(&self) -> TrainingState<Fixed> {
+        TrainingState::decode(&self.training, self.params.len()).expect("training state was checked")
+    }
+
+    /// Check that the checkpoint holds a `model` in the lib's numeric type.
+    pub fn check(&self, model: GuestMode) -> Result<(), CheckpointError> {
+        if self.header.model != model {
+            return Err(CheckpointError::WrongModel { expected: model, found: self.header.model });
+        }
+        if self.header.numeric != NumericType::FIXED {
+            return Err(CheckpointError::WrongNumericType { expected: NumericType::FIXED, found: self.header.numeric });
+        }
+
+        Ok(())
+    }
+
+    /// The weights and the bias of a perceptron checkpoint, as `(w_l, w_r, b)`.
+    pub fn to_perceptron(&self) -> Result<(u32, u32, u32), CheckpointError> {
+        self.check(GuestMode::Perceptron)?;
+        let [w_l, w_r, b] = self.params[..] else { return Err(CheckpointError::InvalidArchitecture) };
+
+        Ok((w_l, w_r, b))
+    }
+
+    pub fn to_mlp(&self) -> Result<MLP, CheckpointError> {
+        self.check(GuestMode::Mlp)?;
+        let shape = self.header.architecture[..].try_into().map_err(|_| CheckpointError::InvalidArchitecture)?;
+
+        Ok(MLP::from_params(shape, &self.params))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let CheckpointHeader { version, model, numeric, architecture } = &self.header;
+        let mut bytes = Vec::with_capacity(4 * (7 + architecture.len() + self.params.len() + self.training.len()) + 32);
+        bytes.extend_from_slice(&MAGIC);
+        for word in [*version, model.to_u32(), numeric.to_u32()] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        for words in [architecture, &self.params, &self.training] {
+            bytes.extend_from_slice(&(words.len() as u32).to_le_bytes());
+            for word in words {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&self.digest);
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CheckpointError> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != MAGIC {
+            return Err(CheckpointError::BadMagic);
+        }
+
+        let version = reader.word()?;
+        if version != Self::VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+        let model = reader.word()?;
+        let model = GuestMode::from_u32(model).ok_or(CheckpointError::UnknownModel(model))?;
+        let numeric = reader.word()?;
+        let numeric = NumericType::from_u32(numeric).ok_or(CheckpointError::UnknownNumericType(numeric))?;
+        let architecture = reader.words()?;
+        let params = reader.words()?;
+        let training = reader.words()?;
+        let digest = reader.take(32)?.try_into().expect("took 32 bytes");
+        if !reader.0.is_empty() {
+            return Err(CheckpointError::TrailingBytes);
+        }
+
+        let header = CheckpointHeader { version, model, numeric, architecture };
+        UncheckedCheckpoint { header, params, training, digest }.try_into()
+    }
+}
+
+impl TryFrom<UncheckedCheckpoint> for Checkpoint {
+    type Error = CheckpointError;
+
+    fn try_from(unchecked: UncheckedCheckpoint) -> Result<Self, Self::Error> {
+        let UncheckedCheckpoint { header, params, training, digest } = unchecked;
+        if header.version != Self::VERSION {
+            return Err(CheckpointError::UnsupportedVersion(header.version));
+        }
+        if digest != self::digest(&header, &params, &training) {
+            return Err(CheckpointError::DigestMismatch);
+        }
+        if param_count(&header) != Some(params.len()) {
+            return Err(CheckpointError::InvalidArchitecture);
+        }
+        if TrainingState::<Fixed>::decode(&training, params.len()).is_none() {
+            return Err(CheckpointError::InvalidTrainingState);
+        }
+
+        Ok(Self { header, params, training, digest })
+    }
+}
+
+fn digest(header: &CheckpointHeader, params: &[u32], training: &[u32]) -> [u8; 32] {
+    let fields = [header.version, header.model.to_u32(), header.numeric.to_u32()];
+    tagged_digest(CHECKPOINT_TAG, &[&fields, &header.architecture, params, training])
+}
+
+// The number of parameters of the model described by `header`, or `None` if the architecture is
+// malformed.
+fn param_count(header: &CheckpointHeader) -> Option<usize> {
+    let architecture = &header.architecture;
+    match header.model {
+        GuestMode::Perceptron if architecture.is_empty() => Some(3),
+        GuestMode::Sequential => {
+            let specs = LayerSpec::decode_all(architecture)?;
+            LayerSpec::total_params(&specs)
+        }
+        GuestMode::Mlp => {
+            // Counted in `u32`, like the layers of a `Sequential` model.
+            let [input, hidden, output]: [u32; 3] = architecture[..].try_into().ok()?;
+            let first = input.checked_add(1)?.checked_mul(hidden)?;
+            let second = hidden.checked_add(1)?.checked_mul(output)?;
+            Some(first.checked_add(second)? as usize)
+        }
+        _ => None,
+    }
+}
+
+// Reads little-endian words from the front of a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CheckpointError> {
+        if self.0.len() < n {
+            return Err(CheckpointError::Truncated);
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+
+        Ok(head)
+    }
+
+    fn word(&mut self) -> Result<u32, CheckpointError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("took 4 bytes")))
+    }
+
+    // A length word followed by that many words.
+    fn words(&mut self) -> Result<Vec<u32>, CheckpointError> {
+        let len = self.word()? as usize;
+        if self.0.len() / 4 < len {
+            return Err(CheckpointError::Truncated);
+        }
+
+        (0..len).map(|_| self.word()).collect()
+    }
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::BadMagic => write!(f, "not a checkpoint"),
+            CheckpointError::UnsupportedVersion(v) => write!(f, "unsupported checkpoint version {}", v),
+            CheckpointError::Truncated => write!(f, "truncated checkpoint"),
+            CheckpointError::TrailingBytes => write!(f, "trailing bytes after checkpoint"),
+            CheckpointError::UnknownModel(i) => write!(f, "unknown model {} in checkpoint", i),
+            CheckpointError::UnknownNumericType(i) => write!(f, "unknown numeric type {} in checkpoint", i),
+            CheckpointError::DigestMismatch => write!(f, "checkpoint digest does not match its contents"),
+            CheckpointError::InvalidArchitecture => write!(f, "checkpoint parameters do not fit its architecture"),
+            CheckpointError::InvalidTrainingState => write!(f, "checkpoint training state does not fit its parameters"),
+            CheckpointError::WrongModel { expected, found } => {
+                write!(f, "expected a {:?} checkpoint, found a {:?} checkpoint", expected, found)
+            }
+            CheckpointError::WrongNumericType { expected, found } => {
+                write!(f, "expected {:?} parameters, found {:?} parameters", expected, found)
+            }
+        }
+    }
+}
+
+impl core::error::Error for CheckpointError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use crate::optim::{Adam, AnyOptimizer};
+    use crate::{ActivationKind, LossKind, Scalar, Schedule, Sequential};
+
+    fn checkpoints() -> [Checkpoint; 3] {
+        let raw = |x: f32| Fixed::from_f32(x).to_raw();
+        let mlp = MLP::fill(4, 2, 3, 7);
+        let specs = [LayerSpec::new(2, 3, ActivationKind::Sigmoid), LayerSpec::new(3, 1, ActivationKind::Identity)];
+        let params = Sequential::<Fixed>::seeded(&specs, 5).params().into_iter().map(Fixed::to_raw).collect();
+        let fixed = Fixed::from_f32;
+        let adam = TrainingState {
+            loss: LossKind::BinaryCrossEntropy,
+            optimizer: AnyOptimizer::Adam(Adam::new(fixed(0.9), fixed(0.999), fixed(1e-2), 13)),
+            schedule: Schedule::StepDecay { every: 2, factor: fixed(0.5) },
+            batch_size: 2,
+            epoch: 4,
+        };
+
+        [
+            Checkpoint::perceptron(raw(0.5), raw(-0.25), raw(0.1)),
+            Checkpoint::from_mlp(&mlp),
+            Checkpoint::new(GuestMode::Sequential, LayerSpec::encode_all(&specs), params).with_training(&adam),
+        ]
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        for checkpoint in checkpoints() {
+            assert_eq!(Checkpoint::from_bytes(&checkpoint.to_bytes()), Ok(checkpoint.clone()));
+        }
+
+        let [perceptron, mlp, _] = checkpoints();
+        let perceptron = Checkpoint::from_bytes(&perceptron.to_bytes()).unwrap();
+        assert_eq!(perceptron.to_perceptron(), Ok((perceptron.params[0], perceptron.params[1], perceptron.params[2])));
+        assert_eq!(Checkpoint::from_bytes(&mlp.to_bytes()).unwrap().to_mlp(), Ok(MLP::fill(4, 2, 3, 7)));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        for checkpoint in checkpoints() {
+            let json = serde_json::to_string(&checkpoint).unwrap();
+            let decoded: Checkpoint = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded, checkpoint);
+            assert_eq!(decoded.training(), checkpoint.training());
+        }
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = checkpoints()[0].to_bytes();
+        bytes[0] = b'X';
+        assert_eq!(Checkpoint::from_bytes(&bytes), Err(CheckpointError::BadMagic));
+    }
+
+    #[test]
+    fn rejects_wrong_version() {
+        for checkpoint in checkpoints() {
+            let mut bytes = checkpoint.to_bytes();
+            bytes[4..8].copy_from_slice(&2u32.to_le_bytes());
+            assert_eq!(Checkpoint::from_bytes(&bytes), Err(CheckpointError::UnsupportedVersion(2)));
+
+            let mut json = serde_json::to_value(&checkpoint).unwrap();
+            json["header"]["version"] = 4.into();
+            let error = serde_json::from_value::<Checkpoint>(json).unwrap_err();
+            assert_eq!(error.to_string(), CheckpointError::UnsupportedVersion(4).to_string());
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_digest() {
+        for checkpoint in checkpoints() {
+            let bytes = checkpoint.to_bytes();
+
+            // A flipped bit anywhere after the version, digest included.
+            for i in 8..bytes.len() {
+                let mut tampered = bytes.clone();
+                tampered[i] ^= 1;
+                assert!(Checkpoint::from_bytes(&tampered).is_err(), "byte {}", i);
+            }
+            let mut tampered = bytes.clone();
+            let last = tampered.len() - 1;
+            tampered[last] ^= 1;
+            assert_eq!(Checkpoint::from_bytes(&tampered), Err(CheckpointError::DigestMismatch));
+
+            let mut json = serde_json::to_value(&checkpoint).unwrap();
+            json["params"][0] = (checkpoint.params()[0] ^ 1).into();
+            let error = serde_json::from_value::<Checkpoint>(json).unwrap_err();
+            assert_eq!(error.to_string(), CheckpointError::DigestMismatch.to_string());
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_and_trailing_bytes() {
+        for checkpoint in checkpoints() {
+            let bytes = checkpoint.to_bytes();
+            for len in 4..bytes.len() {
+                assert_eq!(Checkpoint::from_bytes(&bytes[..len]), Err(CheckpointError::Truncated), "{} bytes", len);
+            }
+
+            let mut long = bytes.clone();
+            long.push(0);
+            assert_eq!(Checkpoint::from_bytes(&long), Err(CheckpointError::TrailingBytes));
+        }
+    }
+
+    #[test]
+    fn rejects_disconnected_and_empty_architectures() {
+        let (relu, identity) = (ActivationKind::Relu.to_u32(), ActivationKind::Identity.to_u32());
+        for (architecture, num_params) in [(alloc::vec![2, 4, relu, 3, 1, identity], 16), (Vec::new(), 0)] {
+            // Encoded with a valid digest, as a checkpoint written by another tool could be.
+            let (version, numeric) = (Checkpoint::VERSION, NumericType::FIXED);
+            let header = CheckpointHeader { version, model: GuestMode::Sequential, numeric, architecture };
+            let (params, training) = (alloc::vec![0; num_params], TrainingState::<Fixed>::sgd().encode());
+            let digest = digest(&header, &params, &training);
+            let bytes = Checkpoint { header, params, training, digest }.to_bytes();
+
+            assert_eq!(Checkpoint::from_bytes(&bytes), Err(CheckpointError::InvalidArchitecture));
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_architectures() {
+        let training = TrainingState::<Fixed>::sgd().encode();
+        for (model, architecture) in [
+            (GuestMode::Mlp, alloc::vec![u32::MAX, 2, 3]),
+            (GuestMode::Mlp, alloc::vec![1 << 16, 1 << 16, 1]),
+            (GuestMode::Sequential, LayerSpec::encode_all(&[LayerSpec::new(1 << 16, 1 << 16, ActivationKind::Relu)])),
+        ] {
+            let (version, numeric) = (Checkpoint::VERSION, NumericType::FIXED);
+            let header = CheckpointHeader { version, model, numeric, architecture };
+            assert_eq!(param_count(&header), None, "{:?}", header.architecture);
+
+            let digest = digest(&header, &[], &training);
+            let unchecked = UncheckedCheckpoint { header, params: Vec::new(), training: training.clone(), digest };
+            assert_eq!(Checkpoint::try_from(unchecked), Err(CheckpointError::InvalidArchitecture));
+        }
+    }
+}
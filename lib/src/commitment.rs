@@ -0,0 +1,59 @@
+//! SHA-256 commitments to weights.
+//!
+//! When a run is proven with [`Commitment::Digest`], the weights are read from private stdin and
+//! only the digests of the checkpoints before and after training appear in the public values, next
+//! to the Merkle root of the dataset. Anyone holding a checkpoint can recompute
+//! [`Checkpoint::digest`](crate::Checkpoint::digest) and compare it with the committed value, while
+//! a verifier without it learns nothing beyond the digest.
+//!
+//! Digests are `SHA-256(tag || len(a) || a || len(b) || b || ...)` over lists of words, where all
+//! lengths and words are little-endian `u32`s and the tag separates what is hashed: checkpoints,
+//! optimizer states and inference inputs.
+
+use sha2::{Digest, Sha256};
+
+const INPUT_TAG: &[u8] = b"zkpot/input/v1";
+
+/// How a guest commits to the weights it trained.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Commitment {
+    /// Commit the raw weights.
+    Clear,
+    /// Commit SHA-256 digests of the weights.
+    Digest,
+}
+
+impl Commitment {
+    pub fn to_u32(self) -> u32 {
+        match self {
+            Commitment::Clear => 0,
+            Commitment::Digest => 1,
+        }
+    }
+
+    pub fn from_u32(i: u32) -> Option<Self> {
+        match i {
+            0 => Some(Commitment::Clear),
+            1 => Some(Commitment::Digest),
+            _ => None,
+        }
+    }
+}
+
+/// Digest of a raw model input, committed by inference runs.
+pub fn input_digest(input: &[u32]) -> [u8; 32] {
+    tagged_digest(INPUT_TAG, &[input])
+}
+
+pub(crate) fn tagged_digest(tag: &[u8], parts: &[&[u32]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(tag);
+    for &words in parts {
+        hasher.update((words.len() as u32).to_le_bytes());
+        for word in words {
+            hasher.update(word.to_le_bytes());
+        }
+    }
+
+    hasher.finalize().into()
+}
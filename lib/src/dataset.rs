@@ -0,0 +1,169 @@
+//! The built-in datasets.
+//!
+//! A dataset is selected on stdin by the id of its [`DatasetId`]. Ids are checked with
+//! [`TryFrom<u32>`] rather than mapped to a default, so a proof that commits to a dataset id always
+//! trained on that dataset. [`EXTERNAL_DATASET`] is reserved for examples read from stdin.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::{Fixed, Scalar};
+
+// The dataset id that tells the guest to read its examples from stdin instead of using a
+// built-in dataset. The examples are committed through their Merkle root.
+pub const EXTERNAL_DATASET: u32 = u32::MAX;
+
+const ZERO: f32 = 0.0;
+const ONE: f32 = 1.0;
+
+static AND: [[f32; 3]; 4] = [
+    [ZERO, ZERO, ZERO],
+    [ZERO, ONE, ZERO],
+    [ONE, ZERO, ZERO],
+    [ONE, ONE, ONE],
+];
+
+static NAND: [[f32; 3]; 4] = [
+    [ZERO, ZERO, ONE],
+    [ZERO, ONE, ONE],
+    [ONE, ZERO, ONE],
+    [ONE, ONE, ZERO],
+];
+
+static OR: [[f32; 3]; 4] = [
+    [ZERO, ZERO, ZERO],
+    [ZERO, ONE, ONE],
+    [ONE, ZERO, ONE],
+    [ONE, ONE, ONE],
+];
+
+static NOR: [[f32; 3]; 4] = [
+    [ZERO, ZERO, ONE],
+    [ZERO, ONE, ZERO],
+    [ONE, ZERO, ZERO],
+    [ONE, ONE, ZERO],
+];
+
+static XOR: [[f32; 3]; 4] = [
+    [ZERO, ZERO, ZERO],
+    [ZERO, ONE, ONE],
+    [ONE, ZERO, ONE],
+    [ONE, ONE, ZERO],
+];
+
+static MLP_TEST: [[f32; 7]; 1] = [
+    [1.0, 2.0, 3.0, 4.0, 1.0, 0.0, 0.0]
+];
+
+/// A built-in dataset. The logic datasets hold two inputs and one target per example, the MLP test
+/// dataset four features and a one-hot label over three classes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatasetId {
+    And,
+    Nand,
+    Or,
+    Nor,
+    Xor,
+    MlpTest,
+}
+
+/// Why a dataset could not be selected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DatasetError {
+    UnknownId(u32),
+    UnknownName(String),
+}
+
+impl DatasetId {
+    pub const ALL: [DatasetId; 6] =
+        [DatasetId::And, DatasetId::Nand, DatasetId::Or, DatasetId::Nor, DatasetId::Xor, DatasetId::MlpTest];
+
+    pub fn to_u32(self) -> u32 {
+        match self {
+            DatasetId::And => 0,
+            DatasetId::Nand => 1,
+            DatasetId::Or => 2,
+            DatasetId::Nor => 3,
+            DatasetId::Xor => 4,
+            DatasetId::MlpTest => 5,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DatasetId::And => "and",
+            DatasetId::Nand => "nand",
+            DatasetId::Or => "or",
+            DatasetId::Nor => "nor",
+            DatasetId::Xor => "xor",
+            DatasetId::MlpTest => "mlp-test",
+        }
+    }
+
+    /// The number of values in each example, inputs and targets together.
+    pub fn width(self) -> usize {
+        match self {
+            DatasetId::MlpTest => 7,
+            _ => 3,
+        }
+    }
+
+    /// The number of targets at the end of each example.
+    pub fn targets(self) -> usize {
+        match self {
+            DatasetId::MlpTest => 3,
+            _ => 1,
+        }
+    }
+
+    /// The examples as raw values of the lib's numeric type.
+    pub fn rows(self) -> Vec<Vec<u32>> {
+        match self {
+            DatasetId::And => encode(&AND),
+            DatasetId::Nand => encode(&NAND),
+            DatasetId::Or => encode(&OR),
+            DatasetId::Nor => encode(&NOR),
+            DatasetId::Xor => encode(&XOR),
+            DatasetId::MlpTest => encode(&MLP_TEST),
+        }
+    }
+}
+
+fn encode<const N: usize>(table: &[[f32; N]]) -> Vec<Vec<u32>> {
+    table.iter().map(|example| example.iter().map(|&v| Fixed::from_f32(v).to_raw()).collect()).collect()
+}
+
+impl TryFrom<u32> for DatasetId {
+    type Error = DatasetError;
+
+    fn try_from(i: u32) -> Result<Self, Self::Error> {
+        DatasetId::ALL.into_iter().find(|d| d.to_u32() == i).ok_or(DatasetError::UnknownId(i))
+    }
+}
+
+impl FromStr for DatasetId {
+    type Err = DatasetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DatasetId::ALL.into_iter().find(|d| d.name() == s).ok_or_else(|| DatasetError::UnknownName(s.into()))
+    }
+}
+
+impl fmt::Display for DatasetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasetError::UnknownId(i) => write!(f, "unknown dataset id {}", i),
+            DatasetError::UnknownName(name) => write!(f, "unknown dataset `{}`", name),
+        }
+    }
+}
+
+impl core::error::Error for DatasetError {}
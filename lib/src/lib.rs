@@ -1,406 +1,944 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
 use alloy_sol_types::sol;
-//use fixed::types::I16F16;
-
-type Fixed = f32;
-
-const ZERO: f32 = 0.0;
-const ONE: f32 = 1.0;
-
-pub enum Dataset {
-    AND,
-    NAND,
-    OR,
-    NOR,
-    XOR
-}
-
-static AND: [[Fixed; 3]; 4] = [
-    [ZERO, ZERO, ZERO],
-    [ZERO, ONE, ZERO],
-    [ONE, ZERO, ZERO],
-    [ONE, ONE, ONE],
-];
-
-static NAND: [[Fixed; 3]; 4] = [
-    [ZERO, ZERO, ONE],
-    [ZERO, ONE, ONE],
-    [ONE, ZERO, ONE],
-    [ONE, ONE, ZERO],
-];
-
-static OR: [[Fixed; 3]; 4] = [
-    [ZERO, ZERO, ZERO],
-    [ZERO, ONE, ONE],
-    [ONE, ZERO, ONE],
-    [ONE, ONE, ONE],
-];
-
-static NOR: [[Fixed; 3]; 4] = [
-    [ZERO, ZERO, ONE],
-    [ZERO, ONE, ZERO],
-    [ONE, ZERO, ZERO],
-    [ONE, ONE, ZERO],
-];
-
-static XOR: [[Fixed; 3]; 4] = [
-    [ZERO, ZERO, ZERO],
-    [ZERO, ONE, ONE],
-    [ONE, ZERO, ONE],
-    [ONE, ONE, ZERO],
-];
-
-pub fn u32_to_dataset(i: u32) -> Dataset {
-    match i {
-        0 => Dataset::AND,
-        1 => Dataset::NAND,
-        2 => Dataset::OR,
-        3 => Dataset::NOR,
-        4 => Dataset::XOR,
-
-        // If we are not given a valid dataset, return AND by default
-        _ => Dataset::AND
-    }
-}
-
-pub fn get_dataset(dataset: Dataset) -> [[Fixed; 3]; 4] {
-    match dataset {
-        Dataset::AND => AND,
-        Dataset::NAND => NAND,
-        Dataset::OR => OR,
-        Dataset::NOR => NOR,
-        Dataset::XOR => XOR
+use serde::{Deserialize, Serialize};
+
+pub mod activation;
+pub mod checkpoint;
+pub mod commitment;
+pub mod dataset;
+pub mod loss;
+pub mod merkle;
+pub mod metrics;
+pub mod numeric;
+pub mod optim;
+pub mod sequential;
+pub mod tensor;
+
+pub use activation::{Activation, ActivationKind};
+pub use checkpoint::{Checkpoint, CheckpointError};
+pub use commitment::{input_digest, Commitment};
+pub use dataset::{DatasetError, DatasetId, EXTERNAL_DATASET};
+pub use loss::{Loss, LossKind};
+pub use merkle::MerkleTree;
+pub use metrics::Metrics;
+pub use numeric::{Fixed, NumericType, Scalar};
+pub use optim::{Optimizer, OptimizerKind, Schedule, Trainable, TrainingState};
+pub use sequential::{LayerSpec, Sequential};
+pub use tensor::Tensor;
+
+// The first word the guest reads from stdin, selecting which training routine it proves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuestMode {
+    Perceptron,
+    Sequential,
+    Mlp,
+    Infer,
+}
+
+impl GuestMode {
+    pub fn to_u32(self) -> u32 {
+        match self {
+            GuestMode::Perceptron => 0,
+            GuestMode::Sequential => 1,
+            GuestMode::Mlp => 2,
+            GuestMode::Infer => 3,
+        }
+    }
+
+    pub fn from_u32(i: u32) -> Option<Self> {
+        match i {
+            0 => Some(GuestMode::Perceptron),
+            1 => Some(GuestMode::Sequential),
+            2 => Some(GuestMode::Mlp),
+            3 => Some(GuestMode::Infer),
+            _ => None,
+        }
     }
 }
 
+// Convert raw examples into rows of a fixed width, panicking on a malformed example.
+fn decode_rows<const N: usize>(rows: &[Vec<u32>]) -> Vec<[Fixed; N]> {
+    rows.iter()
+        .map(|row| {
+            let row: [u32; N] = row.as_slice().try_into().expect("example has the wrong number of values");
+            row.map(Fixed::from_raw)
+        })
+        .collect()
+}
+
 sol! {
+    /// The loss and accuracy of the trained model on the examples with Merkle root
+    /// `dataset_root`, which is either the training set or a held-out set. `loss_function` is the
+    /// loss the model was trained with, 0 for the squared error, 1 for binary cross-entropy and 2
+    /// for softmax cross-entropy. `loss` is its mean over the examples as a raw value of the lib's
+    /// numeric type, and the accuracy is `correct / total`.
+    #[derive(Debug, PartialEq, Eq)]
+    struct EvaluationStruct {
+        bytes32 dataset_root;
+        uint32 loss_function;
+        uint32 loss;
+        uint32 correct;
+        uint32 total;
+    }
+
+    /// The optimizer of a training run, as encoded by `TrainingState::encode`. `kind` is 0 for
+    /// SGD, 1 for momentum and 2 for Adam, and `schedule` is 0 for a constant learning rate, 1 for
+    /// step decay and 2 for cosine decay. Hyperparameters are raw values of the lib's numeric type,
+    /// zero where the optimizer does not use them. `epoch` is the number of epochs trained before
+    /// the run, and the digests cover the whole training state, loss included, before and after
+    /// the run.
+    #[derive(Debug, PartialEq, Eq)]
+    struct OptimizerStruct {
+        uint32 kind;
+        uint32 batch_size;
+        uint32 schedule;
+        uint32 schedule_epochs;
+        uint32 schedule_factor;
+        uint32 beta1;
+        uint32 beta2;
+        uint32 epsilon;
+        uint32 epoch;
+        bytes32 state_digest;
+        bytes32 updated_state_digest;
+    }
+
     /// The public values encoded as a struct that can be easily deserialized inside Solidity.
     struct PublicValuesStruct {
         uint32 d;
+        bytes32 dataset_root;
         uint32 t;
         uint32 w_l;
         uint32 w_r;
+        uint32 b;
         uint32 r;
 
         uint32 updated_w_l;
         uint32 updated_w_r;
+        uint32 updated_b;
+
+        OptimizerStruct optimizer;
+        EvaluationStruct evaluation;
+    }
+
+    /// The public values of a `Sequential` training run. `layers` holds one
+    /// `(input, output, activation)` triple per layer.
+    struct SequentialPublicValuesStruct {
+        uint32[] layers;
+        uint32 d;
+        bytes32 dataset_root;
+        uint32 t;
+        uint32 r;
+
+        uint32[] params;
+        uint32[] updated_params;
+
+        OptimizerStruct optimizer;
+        EvaluationStruct evaluation;
+    }
+
+    /// The public values of an `MLP` training run. `shape` is `[input, hidden, output]` and the
+    /// parameters are laid out as `w1, b1, w2, b2`.
+    struct MlpPublicValuesStruct {
+        uint32[] shape;
+        uint32 d;
+        bytes32 dataset_root;
+        uint32 t;
+        uint32 r;
+
+        uint32[] params;
+        uint32[] updated_params;
+
+        OptimizerStruct optimizer;
+        EvaluationStruct evaluation;
+    }
+
+    /// The public values of a run that keeps its weights and dataset private. Only the digests of
+    /// the checkpoints before and after training, which cover the weights and the optimizer state,
+    /// and the Merkle root of the dataset are committed.
+    struct DigestPublicValuesStruct {
+        uint32 mode;
+        uint32 t;
+        uint32 r;
+
+        bytes32 dataset_root;
+        bytes32 weights_digest;
+        bytes32 updated_weights_digest;
+
+        EvaluationStruct evaluation;
+    }
+
+    /// The public values of an aggregated chain of `DigestPublicValuesStruct` segments. `vkey` is
+    /// the hash of the training program's verifying key, `t` the total number of epochs, and the
+    /// digests are those of the first and last checkpoints of the chain. The evaluation is the one of
+    /// the last segment, that is of the final weights.
+    struct AggregatePublicValuesStruct {
+        bytes32 vkey;
+        uint32 mode;
+        uint32 t;
+        uint32 r;
+
+        bytes32 dataset_root;
+        bytes32 weights_digest;
+        bytes32 updated_weights_digest;
+
+        EvaluationStruct evaluation;
+    }
+}
+
+sol! {
+    /// The public values of an inference run: which model made the prediction, the digest of its
+    /// checkpoint, the digest of the input, and the raw predicted output.
+    struct InferencePublicValuesStruct {
+        uint32 model;
+        bytes32 weights_digest;
+        bytes32 input_digest;
+
+        uint32[] output;
     }
 }
 
-pub fn heaviside(n: Fixed) -> Fixed {
-    let zero = ZERO;
+impl EvaluationStruct {
+    // Commit to the metrics of a model evaluated with `loss` on the examples with the given Merkle
+    // root.
+    pub fn new(dataset_root: [u8; 32], loss: LossKind, metrics: Metrics<Fixed>) -> Self {
+        Self {
+            dataset_root: dataset_root.into(),
+            loss_function: loss.to_u32(),
+            loss: metrics.loss.to_raw(),
+            correct: metrics.correct,
+            total: metrics.total,
+        }
+    }
+}
 
-    if zero.lt(&n) {
-        return ZERO
-    } else {
-        return ONE
+impl OptimizerStruct {
+    // Commit to the optimizer of a run that started from `training` and ended with `updated`.
+    pub fn new(training: &TrainingState<Fixed>, updated: &TrainingState<Fixed>) -> Self {
+        let words = training.encode();
+        let [kind, batch_size, schedule, schedule_epochs, schedule_factor, beta1, beta2, epsilon, epoch] =
+            words[..9].try_into().expect("the encoding starts with the nine words of the optimizer");
+
+        Self {
+            kind,
+            batch_size,
+            schedule,
+            schedule_epochs,
+            schedule_factor,
+            beta1,
+            beta2,
+            epsilon,
+            epoch,
+            state_digest: training.digest().into(),
+            updated_state_digest: updated.digest().into(),
+        }
     }
 }
 
-// Train t epochs on the provided dataset, the learning rate, and the given weights.
-pub fn update_perceptron(d: u32, t: u32, r: u32, w_l: u32, w_r: u32) -> (u32, u32) {
-    let dataset = get_dataset(u32_to_dataset(d));
-    let learning_rate = Fixed::from_bits(r);
-    let mut weight_left = Fixed::from_bits(w_l);
-    let mut weight_right = Fixed::from_bits(w_r);
+impl DigestPublicValuesStruct {
+    // Commit to a training run from `checkpoint` to `updated`.
+    pub fn new(
+        dataset_root: [u8; 32],
+        t: u32,
+        r: u32,
+        checkpoint: &Checkpoint,
+        updated: &Checkpoint,
+        evaluation: EvaluationStruct,
+    ) -> Self {
+        Self {
+            mode: checkpoint.model().to_u32(),
+            t,
+            r,
+            dataset_root: dataset_root.into(),
+            weights_digest: checkpoint.digest().into(),
+            updated_weights_digest: updated.digest().into(),
+            evaluation,
+        }
+    }
+}
 
-    for _ in 0..t {
-        for example in dataset {
-            
-            // Unpack the dataset and model
-            let left = example[0];
-            let right = example[1];
-            let bias = ONE;
+impl AggregatePublicValuesStruct {
+    // Check that `segments` form a single training run, each one resuming from the weights the
+    // previous one ended with, on the same dataset and learning rate. `vkey` is the verifying key
+    // hash the segments were proven with, as eight words.
+    pub fn chain(vkey: [u32; 8], segments: &[DigestPublicValuesStruct]) -> Result<Self, ChainError> {
+        let (first, last) = match segments {
+            [first, .., last] => (first, last),
+            [single] => (single, single),
+            [] => return Err(ChainError::Empty),
+        };
+
+        let mut t = 0u32;
+        for (i, segment) in segments.iter().enumerate() {
+            if segment.mode != first.mode {
+                return Err(ChainError::WrongModel(i));
+            }
+            if segment.r != first.r {
+                return Err(ChainError::WrongLearningRate(i));
+            }
+            if segment.dataset_root != first.dataset_root {
+                return Err(ChainError::WrongDataset(i));
+            }
+            if i > 0 && segment.weights_digest != segments[i - 1].updated_weights_digest {
+                return Err(ChainError::BrokenLink(i));
+            }
+            t = t.checked_add(segment.t).ok_or(ChainError::TooManyEpochs)?;
+        }
 
-            let truth = example[2];
+        Ok(Self {
+            vkey: Self::vkey_bytes(vkey).into(),
+            mode: first.mode,
+            t,
+            r: first.r,
+            dataset_root: first.dataset_root,
+            weights_digest: first.weights_digest,
+            updated_weights_digest: last.updated_weights_digest,
+            evaluation: last.evaluation.clone(),
+        })
+    }
 
-            // Run a model prediction
-            let predicted = heaviside(weight_left * left + weight_right * right + bias);
+    /// The `vkey` committed for segments proven with the verifying key hash `vkey`, as eight words.
+    /// A verifier must check it against the hash of the training program it expects, since the
+    /// aggregation program verifies the segments with whatever key it is given.
+    pub fn vkey_bytes(vkey: [u32; 8]) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(vkey) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        bytes
+    }
+}
 
-            // Update weights
-            weight_left = weight_left - learning_rate * (predicted - truth) * left;
-            weight_right = weight_right - learning_rate * (predicted - truth) * right;
+/// Why segments do not chain into a single training run. Indices are those of the offending
+/// segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainError {
+    Empty,
+    WrongModel(usize),
+    WrongLearningRate(usize),
+    WrongDataset(usize),
+    /// The segment does not resume from the checkpoint the previous one ended with.
+    BrokenLink(usize),
+    TooManyEpochs,
+}
+
+impl core::fmt::Display for ChainError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ChainError::Empty => write!(f, "nothing to aggregate"),
+            ChainError::WrongModel(i) => write!(f, "segment {} trains another model", i),
+            ChainError::WrongLearningRate(i) => write!(f, "segment {} uses another learning rate", i),
+            ChainError::WrongDataset(i) => write!(f, "segment {} trains on another dataset", i),
+            ChainError::BrokenLink(i) => write!(f, "segment {} does not resume from segment {}", i, i - 1),
+            ChainError::TooManyEpochs => write!(f, "too many epochs"),
         }
     }
-    
-    return (weight_left.to_bits(), weight_right.to_bits())
 }
 
+impl core::error::Error for ChainError {}
+
+// The step function: one for positive inputs, zero otherwise.
+pub fn heaviside<T: Scalar>(n: T) -> T {
+    activation::Step.forward(n)
+}
+
+/// Train t epochs on the provided dataset, the learning rate, and the given weights and bias.
+///
+/// The perceptron learns every linearly separable logic dataset:
+///
+/// ```
+/// use fibonacci_lib::{perceptron_forward, update_perceptron, DatasetId, Fixed, Scalar};
+///
+/// let raw = |v: f32| Fixed::from_f32(v).to_raw();
+/// for d in [DatasetId::And, DatasetId::Or, DatasetId::Nand, DatasetId::Nor] {
+///     let (w_l, w_r, b) = update_perceptron(d, 50, raw(0.1), raw(0.1), raw(-0.1), raw(0.0));
+///
+///     for row in d.rows() {
+///         let [left, right, truth] = row[..].try_into().unwrap();
+///         let [w_l, w_r, b, left, right] = [w_l, w_r, b, left, right].map(Fixed::from_raw);
+///         assert_eq!(perceptron_forward(w_l, w_r, b, left, right).to_raw(), truth, "{}", d);
+///     }
+/// }
+/// ```
+pub fn update_perceptron(d: DatasetId, t: u32, r: u32, w_l: u32, w_r: u32, b: u32) -> (u32, u32, u32) {
+    update_perceptron_on(&d.rows(), t, r, w_l, w_r, b)
+}
 
+// Like `update_perceptron`, but on raw examples of the form `[left, right, truth]`.
+pub fn update_perceptron_on(rows: &[Vec<u32>], t: u32, r: u32, w_l: u32, w_r: u32, b: u32) -> (u32, u32, u32) {
+    let [w_l, w_r, b] = update_perceptron_with(rows, t, r, [w_l, w_r, b], &mut TrainingState::sgd());
+    (w_l, w_r, b)
+}
+
+// Like `update_perceptron_on`, but with the optimizer of `training`, which is updated to continue
+// from where this run stops. The parameters are `[w_l, w_r, b]`. The perceptron rule only goes
+// with the squared error, so any other loss panics.
+pub fn update_perceptron_with(
+    rows: &[Vec<u32>],
+    t: u32,
+    r: u32,
+    params: [u32; 3],
+    training: &mut TrainingState<Fixed>,
+) -> [u32; 3] {
+    assert_eq!(training.loss, LossKind::Mse, "a perceptron is trained on the squared error");
+    let mut model = Perceptron::from_params(&params.map(Fixed::from_raw));
+    training.train(&mut model, &decode_rows::<3>(rows), t, Fixed::from_raw(r));
+
+    [model.weight_left, model.weight_right, model.bias].map(Fixed::to_raw)
+}
+
+// Train a `Sequential` model described by encoded layer specs on a built-in dataset.
+pub fn update_sequential(layers: &[u32], d: DatasetId, t: u32, r: u32, params: &[u32]) -> Vec<u32> {
+    update_sequential_on(layers, &d.rows(), t, r, params)
+}
+
+// Like `update_sequential`, but on raw examples holding the inputs followed by the targets.
+pub fn update_sequential_on(layers: &[u32], rows: &[Vec<u32>], t: u32, r: u32, params: &[u32]) -> Vec<u32> {
+    update_sequential_with(layers, rows, t, r, params, &mut TrainingState::sgd())
+}
+
+// Like `update_sequential_on`, but with the loss and optimizer of `training`, which is updated to continue
+// from where this run stops.
+pub fn update_sequential_with(
+    layers: &[u32],
+    rows: &[Vec<u32>],
+    t: u32,
+    r: u32,
+    params: &[u32],
+    training: &mut TrainingState<Fixed>,
+) -> Vec<u32> {
+    let specs = LayerSpec::decode_all(layers).expect("invalid layer specs");
+    let params: Vec<Fixed> = params.iter().copied().map(Fixed::from_raw).collect();
+    let mut model = Sequential::from_params(&specs, &params);
+
+    let dataset = decode_examples(rows, model.input_dim() + model.output_dim());
+    model.train_with(&dataset, t, Fixed::from_raw(r), training);
+
+    model.params().into_iter().map(Fixed::to_raw).collect()
+}
+
+// Train the model of a checkpoint for t epochs on raw examples, with the optimizer state the
+// checkpoint holds. Returns the checkpoint of the trained model and of the updated optimizer
+// state, from which training can be resumed.
+pub fn update_checkpoint(checkpoint: &Checkpoint, rows: &[Vec<u32>], t: u32, r: u32) -> Checkpoint {
+    let mut training = checkpoint.training();
+    let (architecture, params) = (checkpoint.architecture(), checkpoint.params());
+
+    let updated = match checkpoint.model() {
+        GuestMode::Perceptron => {
+            let params = params.try_into().expect("a perceptron has two weights and a bias");
+            update_perceptron_with(rows, t, r, params, &mut training).to_vec()
+        }
+        GuestMode::Sequential => update_sequential_with(architecture, rows, t, r, params, &mut training),
+        GuestMode::Mlp => {
+            let shape = architecture.try_into().expect("MLP shape has three widths");
+            update_mlp_with(MLP::from_params(shape, params), rows, t, r, &mut training).params()
+        }
+        GuestMode::Infer => panic!("inference is not a model"),
+    };
+
+    Checkpoint::new(checkpoint.model(), architecture.to_vec(), updated).with_training(&training)
+}
+
+// The squared error and accuracy of a perceptron on raw examples of the form
+// `[left, right, truth]`.
+pub fn evaluate_perceptron_on(rows: &[Vec<u32>], w_l: u32, w_r: u32, b: u32) -> Metrics<Fixed> {
+    let [w_l, w_r, b] = [w_l, w_r, b].map(Fixed::from_raw);
+    Metrics::score(
+        &LossKind::Mse,
+        decode_rows::<3>(rows)
+            .into_iter()
+            .map(|[left, right, truth]| ([perceptron_forward(w_l, w_r, b, left, right)], [truth])),
+    )
+}
+
+// The `loss` and accuracy of a `Sequential` model on raw examples holding the inputs followed by
+// the targets.
+pub fn evaluate_sequential_on(layers: &[u32], rows: &[Vec<u32>], params: &[u32], loss: LossKind) -> Metrics<Fixed> {
+    let specs = LayerSpec::decode_all(layers).expect("invalid layer specs");
+    let params: Vec<Fixed> = params.iter().copied().map(Fixed::from_raw).collect();
+    let model = Sequential::from_params(&specs, &params);
+    let input = model.input_dim();
+
+    Metrics::score(&loss, decode_examples(rows, input + model.output_dim()).into_iter().map(|example| {
+        let output = model.forward(&Tensor::column(example[..input].to_vec())).output().clone();
+        (output.into_data(), example[input..].to_vec())
+    }))
+}
+
+// Decode raw examples of `columns` values each, panicking on a malformed example.
+fn decode_examples(rows: &[Vec<u32>], columns: usize) -> Vec<Vec<Fixed>> {
+    rows.iter()
+        .map(|row| {
+            assert_eq!(row.len(), columns, "example has the wrong number of values");
+            row.iter().copied().map(Fixed::from_raw).collect()
+        })
+        .collect()
+}
+
+// Train t epochs of a two-input perceptron with a learned bias on any numeric backend.
+pub fn train_perceptron<T: Scalar>(
+    dataset: &[[T; 3]],
+    t: u32,
+    learning_rate: T,
+    weight_left: T,
+    weight_right: T,
+    bias: T,
+) -> (T, T, T) {
+    let mut model = Perceptron { weight_left, weight_right, bias };
+    TrainingState::sgd().train(&mut model, dataset, t, learning_rate);
+
+    (model.weight_left, model.weight_right, model.bias)
+}
+
+// A two-input perceptron with a learned bias, trained with the perceptron rule: the gradient on
+// an example `[left, right, truth]` is `(predicted - truth) * [left, right, 1]`, whatever the
+// loss, since the step function has no useful derivative.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Perceptron<T> {
+    pub weight_left: T,
+    pub weight_right: T,
+    pub bias: T,
+}
+
+impl<T: Scalar> Perceptron<T> {
+    // Rebuild a perceptron from `[weight_left, weight_right, bias]`.
+    pub fn from_params(params: &[T]) -> Self {
+        let [weight_left, weight_right, bias] = params[..] else { panic!("a perceptron has two weights and a bias") };
+        Self { weight_left, weight_right, bias }
+    }
+}
+
+impl<T: Scalar> Trainable<T> for Perceptron<T> {
+    fn params(&self) -> Vec<T> {
+        alloc::vec![self.weight_left, self.weight_right, self.bias]
+    }
+
+    fn set_params(&mut self, params: &[T]) {
+        *self = Self::from_params(params);
+    }
+
+    fn accumulate_gradient(&self, example: &[T], _loss: &impl Loss, grads: &mut [T]) {
+        // Unpack the example and run a model prediction
+        let [left, right, truth] = example[..] else { panic!("an example is [left, right, truth]") };
+        let predicted = perceptron_forward(self.weight_left, self.weight_right, self.bias, left, right);
+
+        let error = predicted - truth;
+        for (g, d) in grads.iter_mut().zip([error * left, error * right, error]) {
+            *g = *g + d;
+        }
+    }
+}
+
+// The perceptron's prediction for one input.
+pub fn perceptron_forward<T: Scalar>(weight_left: T, weight_right: T, bias: T, left: T, right: T) -> T {
+    heaviside(weight_left * left + weight_right * right + bias)
+}
+
+// A two-layer perceptron `x -> relu(W1 x + b1) -> W2 h + b2`, with every parameter stored as the
+// raw bits of a `Fixed`. Layer widths follow from the tensor shapes.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MLP {
-    // W1
-    w1_11: u32,
-    w1_12: u32,
-    w1_13: u32,
-    w1_14: u32,
-    w1_21: u32,
-    w1_22: u32,
-    w1_23: u32,
-    w1_24: u32,
-    w1_31: u32,
-    w1_32: u32,
-    w1_33: u32,
-    w1_34: u32,
-    w1_41: u32,
-    w1_42: u32,
-    w1_43: u32,
-    w1_44: u32,
-
-    // b1
-    b1_1: u32,
-    b1_2: u32,
-    b1_3: u32,
-    b1_4: u32,
-
-    // W2
-    w2_11: u32,
-    w2_12: u32,
-    w2_13: u32,
-    w2_14: u32,
-    w2_21: u32,
-    w2_22: u32,
-    w2_23: u32,
-    w2_24: u32,
-    w2_31: u32,
-    w2_32: u32,
-    w2_33: u32,
-    w2_34: u32,
-
-    // b2
-    b2_1: u32,
-    b2_2: u32,
-    b2_3: u32,
-}
-
-static MLP_TEST: [[Fixed; 7]; 1] = [
-    [1.0, 2.0, 3.0, 4.0, 1.0, 0.0, 0.0]
-];
-
-pub enum MLP_Dataset {
-    MLP_TEST
-}
-
-pub fn u32_to_mlp_ds(i: u32) -> MLP_Dataset {
-    match i {
-        0 => MLP_Dataset::MLP_TEST,
-        _ => panic!("Yeah that ain't it chief"),
-    }
-
-}
-
-pub fn get_mlp_dataset(ds: MLP_Dataset) -> [[Fixed; 7]; 1] {
-    match ds {
-        MLP_Dataset::MLP_TEST => MLP_TEST,
-        _ => panic!("Yeah that ain't gonna work")
-    }
-}
-
-pub fn relu(n: Fixed) -> Fixed {
-    let zero = ZERO;
-    if zero.lt(&n) {
-        return n
-    } else {
-        return ZERO
-    }
-}
-
-pub fn update_mlp(mlp: MLP, d: u32, num_epochs: u32, eta: u32) -> MLP {
+    pub w1: Tensor<u32>,
+    pub b1: Tensor<u32>,
+    pub w2: Tensor<u32>,
+    pub b2: Tensor<u32>,
+}
+
+impl MLP {
+    pub fn new(w1: Tensor<u32>, b1: Tensor<u32>, w2: Tensor<u32>, b2: Tensor<u32>) -> Self {
+        let (hidden, _) = w1.shape();
+        let (output, _) = w2.shape();
+        assert_eq!(b1.shape(), (hidden, 1), "b1 must be a column with one entry per hidden unit");
+        assert_eq!(w2.shape().1, hidden, "w2 must take the hidden layer as input");
+        assert_eq!(b2.shape(), (output, 1), "b2 must be a column with one entry per output");
+
+        Self { w1, b1, w2, b2 }
+    }
+
+    // Every parameter set to the given value, for example `Fixed::ONE.to_raw()`.
+    pub fn fill(input: usize, hidden: usize, output: usize, value: u32) -> Self {
+        Self::new(
+            Tensor::fill(hidden, input, value),
+            Tensor::fill(hidden, 1, value),
+            Tensor::fill(output, hidden, value),
+            Tensor::fill(output, 1, value),
+        )
+    }
+
+    pub fn input_dim(&self) -> usize {
+        self.w1.shape().1
+    }
+
+    pub fn output_dim(&self) -> usize {
+        self.w2.shape().0
+    }
+
+    // The layer widths as `[input, hidden, output]`.
+    pub fn shape(&self) -> [u32; 3] {
+        [self.input_dim() as u32, self.w1.shape().0 as u32, self.output_dim() as u32]
+    }
+
+    // Rebuild a model from its shape and the flat vector returned by `MLP::params`.
+    pub fn from_params(shape: [u32; 3], params: &[u32]) -> Self {
+        let [input, hidden, output] = shape.map(|n| n as usize);
+        let sizes = [hidden * input, hidden, output * hidden, output];
+        assert_eq!(params.len(), sizes.iter().sum::<usize>(), "parameter count does not match shape");
+
+        let (w1, rest) = params.split_at(sizes[0]);
+        let (b1, rest) = rest.split_at(sizes[1]);
+        let (w2, b2) = rest.split_at(sizes[2]);
+
+        Self::new(
+            Tensor::new(hidden, input, w1.to_vec()),
+            Tensor::column(b1.to_vec()),
+            Tensor::new(output, hidden, w2.to_vec()),
+            Tensor::column(b2.to_vec()),
+        )
+    }
+
+    // All parameters as `w1`, `b1`, `w2`, `b2`, each in row-major order. This is the same layout
+    // `Sequential::params` uses for the equivalent two-layer model.
+    pub fn params(&self) -> Vec<u32> {
+        [self.w1.data(), self.b1.data(), self.w2.data(), self.b2.data()].concat()
+    }
+}
+
+pub fn relu<T: Scalar>(n: T) -> T {
+    activation::Relu.forward(n)
+}
+
+// The forward pass of the MLP. Returns the hidden pre-activations, the hidden activations and the
+// output, since backpropagation needs all three.
+pub fn mlp_forward<T: Scalar>(
+    w1: &Tensor<T>,
+    b1: &Tensor<T>,
+    w2: &Tensor<T>,
+    b2: &Tensor<T>,
+    x: &Tensor<T>,
+) -> (Tensor<T>, Tensor<T>, Tensor<T>) {
+    let z1 = w1.matmul(x).add(b1);
+    let h = z1.map(relu);
+    let yhat = w2.matmul(&h).add(b2);
+
+    (z1, h, yhat)
+}
+
+pub fn update_mlp(mlp: MLP, d: DatasetId, num_epochs: u32, eta: u32) -> MLP {
+    update_mlp_on(mlp, &d.rows(), num_epochs, eta)
+}
+
+// Like `update_mlp`, but on raw examples holding the features followed by the one-hot label.
+pub fn update_mlp_on(mlp: MLP, rows: &[Vec<u32>], num_epochs: u32, eta: u32) -> MLP {
+    update_mlp_with(mlp, rows, num_epochs, eta, &mut TrainingState::sgd())
+}
+
+// Like `update_mlp_on`, but with the loss and optimizer of `training`, which is updated to continue from
+// where this run stops.
+pub fn update_mlp_with(
+    mlp: MLP,
+    rows: &[Vec<u32>],
+    num_epochs: u32,
+    eta: u32,
+    training: &mut TrainingState<Fixed>,
+) -> MLP {
     // Training-specific items
-    let dataset = get_mlp_dataset(u32_to_mlp_ds(d));
-    let lr = Fixed::from_bits(eta);
-
-    // Model parameters
-    // W1
-    let mut w1_11 = Fixed::from_bits(mlp.w1_11);
-    let mut w1_12 = Fixed::from_bits(mlp.w1_12);
-    let mut w1_13 = Fixed::from_bits(mlp.w1_13);
-    let mut w1_14 = Fixed::from_bits(mlp.w1_14);
-    let mut w1_21 = Fixed::from_bits(mlp.w1_21);
-    let mut w1_22 = Fixed::from_bits(mlp.w1_22);
-    let mut w1_23 = Fixed::from_bits(mlp.w1_23);
-    let mut w1_24 = Fixed::from_bits(mlp.w1_24);
-    let mut w1_31 = Fixed::from_bits(mlp.w1_31);
-    let mut w1_32 = Fixed::from_bits(mlp.w1_32);
-    let mut w1_33 = Fixed::from_bits(mlp.w1_33);
-    let mut w1_34 = Fixed::from_bits(mlp.w1_34);
-    let mut w1_41 = Fixed::from_bits(mlp.w1_41);
-    let mut w1_42 = Fixed::from_bits(mlp.w1_42);
-    let mut w1_43 = Fixed::from_bits(mlp.w1_43);
-    let mut w1_44 = Fixed::from_bits(mlp.w1_44);
-    // b1
-    let mut b1_1 = Fixed::from_bits(mlp.b1_1);
-    let mut b1_2 = Fixed::from_bits(mlp.b1_2);
-    let mut b1_3 = Fixed::from_bits(mlp.b1_3);
-    let mut b1_4 = Fixed::from_bits(mlp.b1_4);
-    // W2
-    let mut w2_11 = Fixed::from_bits(mlp.w2_11);
-    let mut w2_12 = Fixed::from_bits(mlp.w2_12);
-    let mut w2_13 = Fixed::from_bits(mlp.w2_13);
-    let mut w2_14 = Fixed::from_bits(mlp.w2_14);
-    let mut w2_21 = Fixed::from_bits(mlp.w2_21);
-    let mut w2_22 = Fixed::from_bits(mlp.w2_22);
-    let mut w2_23 = Fixed::from_bits(mlp.w2_23);
-    let mut w2_24 = Fixed::from_bits(mlp.w2_24);
-    let mut w2_31 = Fixed::from_bits(mlp.w2_31);
-    let mut w2_32 = Fixed::from_bits(mlp.w2_32);
-    let mut w2_33 = Fixed::from_bits(mlp.w2_33);
-    let mut w2_34 = Fixed::from_bits(mlp.w2_34);
-    // b2
-    let mut b2_1 = Fixed::from_bits(mlp.b2_1);
-    let mut b2_2 = Fixed::from_bits(mlp.b2_2);
-    let mut b2_3 = Fixed::from_bits(mlp.b2_3);
-
-
-
-    // Forward pass
-    for _ in 0..num_epochs {
-        for example in dataset {
-            // Get x (input data)
-            let x_1 = example[0];
-            let x_2 = example[1];
-            let x_3 = example[2];
-            let x_4 = example[3];
-
-            // Get y (one-hot encoded label)
-            let y_1 = example[4];
-            let y_2 = example[5];
-            let y_3 = example[6];
-
-            // Compute z1
-            let z1_1 = (w1_11 * x_1) + (w1_12 * x_2) + (w1_13 * x_3) + (w1_14 * x_4) + b1_1;
-            let z1_2 = (w1_21 * x_1) + (w1_22 * x_2) + (w1_23 * x_3) + (w1_24 * x_4) + b1_2;
-            let z1_3 = (w1_31 * x_1) + (w1_32 * x_2) + (w1_33 * x_3) + (w1_34 * x_4) + b1_3;
-            let z1_4 = (w1_41 * x_1) + (w1_42 * x_2) + (w1_43 * x_3) + (w1_44 * x_4) + b1_4;
-
-            // Compute h
-            let h_1 = relu(z1_1);
-            let h_2 = relu(z1_2);
-            let h_3 = relu(z1_3);
-            let h_4 = relu(z1_4);
-
-            // Compute yhat
-            let yhat_1 = (w2_11 * h_1) + (w2_12 * h_2) + (w2_13 * h_3) + (w2_14 * h_4) + b2_1;
-            let yhat_2 = (w2_21 * h_1) + (w2_22 * h_2) + (w2_23 * h_3) + (w2_24 * h_4) + b2_2;
-            let yhat_3 = (w2_31 * h_1) + (w2_32 * h_2) + (w2_33 * h_3) + (w2_34 * h_4) + b2_3;
-
-            // Compute updates
-            // W1
-            let dLdw1_11 = -2.0 * heaviside(z1_1) * x_1 * ((w2_11 * (y_1 - yhat_1)) + (w2_21 * (y_2 - yhat_2)) + (w2_31 * (y_3 - yhat_3)));
-            let dLdw1_12 = -2.0 * heaviside(z1_1) * x_2 * ((w2_11 * (y_1 - yhat_1)) + (w2_21 * (y_2 - yhat_2)) + (w2_31 * (y_3 - yhat_3)));
-            let dLdw1_13 = -2.0 * heaviside(z1_1) * x_3 * ((w2_11 * (y_1 - yhat_1)) + (w2_21 * (y_2 - yhat_2)) + (w2_31 * (y_3 - yhat_3)));
-            let dLdw1_14 = -2.0 * heaviside(z1_1) * x_4 * ((w2_11 * (y_1 - yhat_1)) + (w2_21 * (y_2 - yhat_2)) + (w2_31 * (y_3 - yhat_3)));
-            let dLdw1_21 = -2.0 * heaviside(z1_2) * x_1 * ((w2_12 * (y_1 - yhat_1)) + (w2_22 * (y_2 - yhat_2)) + (w2_32 * (y_3 - yhat_3)));
-            let dLdw1_22 = -2.0 * heaviside(z1_2) * x_2 * ((w2_12 * (y_1 - yhat_1)) + (w2_22 * (y_2 - yhat_2)) + (w2_32 * (y_3 - yhat_3)));
-            let dLdw1_23 = -2.0 * heaviside(z1_2) * x_3 * ((w2_12 * (y_1 - yhat_1)) + (w2_22 * (y_2 - yhat_2)) + (w2_32 * (y_3 - yhat_3)));
-            let dLdw1_24 = -2.0 * heaviside(z1_2) * x_4 * ((w2_12 * (y_1 - yhat_1)) + (w2_22 * (y_2 - yhat_2)) + (w2_32 * (y_3 - yhat_3)));
-            let dLdw1_31 = -2.0 * heaviside(z1_3) * x_1 * ((w2_13 * (y_1 - yhat_1)) + (w2_23 * (y_2 - yhat_2)) + (w2_33 * (y_3 - yhat_3)));
-            let dLdw1_32 = -2.0 * heaviside(z1_3) * x_2 * ((w2_13 * (y_1 - yhat_1)) + (w2_23 * (y_2 - yhat_2)) + (w2_33 * (y_3 - yhat_3)));
-            let dLdw1_33 = -2.0 * heaviside(z1_3) * x_3 * ((w2_13 * (y_1 - yhat_1)) + (w2_23 * (y_2 - yhat_2)) + (w2_33 * (y_3 - yhat_3)));
-            let dLdw1_34 = -2.0 * heaviside(z1_3) * x_4 * ((w2_13 * (y_1 - yhat_1)) + (w2_23 * (y_2 - yhat_2)) + (w2_33 * (y_3 - yhat_3)));
-            let dLdw1_41 = -2.0 * heaviside(z1_4) * x_1 * ((w2_14 * (y_1 - yhat_1)) + (w2_24 * (y_2 - yhat_2)) + (w2_34 * (y_3 - yhat_3)));
-            let dLdw1_42 = -2.0 * heaviside(z1_4) * x_2 * ((w2_14 * (y_1 - yhat_1)) + (w2_24 * (y_2 - yhat_2)) + (w2_34 * (y_3 - yhat_3)));
-            let dLdw1_43 = -2.0 * heaviside(z1_4) * x_3 * ((w2_14 * (y_1 - yhat_1)) + (w2_24 * (y_2 - yhat_2)) + (w2_34 * (y_3 - yhat_3)));
-            let dLdw1_44 = -2.0 * heaviside(z1_4) * x_4 * ((w2_14 * (y_1 - yhat_1)) + (w2_24 * (y_2 - yhat_2)) + (w2_34 * (y_3 - yhat_3)));
-            // b1
-            let dLdb1_1 = -2.0 * heaviside(z1_1) * ((w2_11 * (y_1 - yhat_1)) + (w2_21 * (y_2 - yhat_2)) + (w2_31 * (y_3 - yhat_3)));
-            let dLdb1_2 = -2.0 * heaviside(z1_2) * ((w2_12 * (y_1 - yhat_1)) + (w2_22 * (y_2 - yhat_2)) + (w2_32 * (y_3 - yhat_3)));
-            let dLdb1_3 = -2.0 * heaviside(z1_3) * ((w2_13 * (y_1 - yhat_1)) + (w2_23 * (y_2 - yhat_2)) + (w2_33 * (y_3 - yhat_3)));
-            let dLdb1_4 = -2.0 * heaviside(z1_4) * ((w2_14 * (y_1 - yhat_1)) + (w2_24 * (y_2 - yhat_2)) + (w2_34 * (y_3 - yhat_3)));
-            // W2
-            let dLdw2_11 = -2.0 * (y_1 - yhat_1) * h_1;
-            let dLdw2_12 = -2.0 * (y_1 - yhat_1) * h_2;
-            let dLdw2_13 = -2.0 * (y_1 - yhat_1) * h_3;
-            let dLdw2_14 = -2.0 * (y_1 - yhat_1) * h_4;
-            let dLdw2_21 = -2.0 * (y_2 - yhat_2) * h_1;
-            let dLdw2_22 = -2.0 * (y_2 - yhat_2) * h_2;
-            let dLdw2_23 = -2.0 * (y_2 - yhat_2) * h_3;
-            let dLdw2_24 = -2.0 * (y_2 - yhat_2) * h_4;
-            let dLdw2_31 = -2.0 * (y_3 - yhat_3) * h_1;
-            let dLdw2_32 = -2.0 * (y_3 - yhat_3) * h_2;
-            let dLdw2_33 = -2.0 * (y_3 - yhat_3) * h_3;
-            let dLdw2_34 = -2.0 * (y_3 - yhat_3) * h_4;
-            // b2
-            let dLdb2_1 = -2.0 * (y_1 - yhat_1);
-            let dLdb2_2 = -2.0 * (y_2 - yhat_2);
-            let dLdb2_3 = -2.0 * (y_3 - yhat_3);
-
-            // Update parameters
-            // W1
-            w1_11 = w1_11 + (lr * dLdw1_11);
-            w1_12 = w1_12 + (lr * dLdw1_12);
-            w1_13 = w1_13 + (lr * dLdw1_13);
-            w1_14 = w1_14 + (lr * dLdw1_14);
-            w1_21 = w1_21 + (lr * dLdw1_21);
-            w1_22 = w1_22 + (lr * dLdw1_22);
-            w1_23 = w1_23 + (lr * dLdw1_23);
-            w1_24 = w1_24 + (lr * dLdw1_24);
-            w1_31 = w1_31 + (lr * dLdw1_31);
-            w1_32 = w1_32 + (lr * dLdw1_32);
-            w1_33 = w1_33 + (lr * dLdw1_33);
-            w1_34 = w1_34 + (lr * dLdw1_34);
-            w1_41 = w1_41 + (lr * dLdw1_41);
-            w1_42 = w1_42 + (lr * dLdw1_42);
-            w1_43 = w1_43 + (lr * dLdw1_43);
-            w1_44 = w1_44 + (lr * dLdw1_44);
-            // b1
-            b1_1 = b1_1 + (lr * dLdb1_1);
-            b1_2 = b1_2 + (lr * dLdb1_2);
-            b1_3 = b1_3 + (lr * dLdb1_3);
-            b1_4 = b1_4 + (lr * dLdb1_4);
-            // W2
-            w2_11 = w2_11 + (lr * dLdw2_11);
-            w2_12 = w2_12 + (lr * dLdw2_12);
-            w2_13 = w2_13 + (lr * dLdw2_13);
-            w2_14 = w2_14 + (lr * dLdw2_14);
-            w2_21 = w2_21 + (lr * dLdw2_21);
-            w2_22 = w2_22 + (lr * dLdw2_22);
-            w2_23 = w2_23 + (lr * dLdw2_23);
-            w2_24 = w2_24 + (lr * dLdw2_24);
-            w2_31 = w2_31 + (lr * dLdw2_31);
-            w2_32 = w2_32 + (lr * dLdw2_32);
-            w2_33 = w2_33 + (lr * dLdw2_33);
-            w2_34 = w2_34 + (lr * dLdw2_34);
-            // b2
-            b2_1 = b2_1 + (lr * dLdb2_1);
-            b2_2 = b2_2 + (lr * dLdb2_2);
-            b2_3 = b2_3 + (lr * dLdb2_3);
+    let dataset = decode_examples(rows, mlp.input_dim() + mlp.output_dim());
+    let mut model = MlpModel {
+        w1: mlp.w1.map(Fixed::from_raw),
+        b1: mlp.b1.map(Fixed::from_raw),
+        w2: mlp.w2.map(Fixed::from_raw),
+        b2: mlp.b2.map(Fixed::from_raw),
+    };
+
+    training.train(&mut model, &dataset, num_epochs, Fixed::from_raw(eta));
+
+    let MlpModel { w1, b1, w2, b2 } = model;
+    MLP::new(w1.map(Fixed::to_raw), b1.map(Fixed::to_raw), w2.map(Fixed::to_raw), b2.map(Fixed::to_raw))
+}
+
+// The parameters of an `MLP` as numbers, while it is trained.
+struct MlpModel {
+    w1: Tensor<Fixed>,
+    b1: Tensor<Fixed>,
+    w2: Tensor<Fixed>,
+    b2: Tensor<Fixed>,
+}
+
+impl Trainable<Fixed> for MlpModel {
+    fn params(&self) -> Vec<Fixed> {
+        [self.w1.data(), self.b1.data(), self.w2.data(), self.b2.data()].concat()
+    }
+
+    fn set_params(&mut self, params: &[Fixed]) {
+        let (w1, rest) = params.split_at(self.w1.data().len());
+        let (b1, rest) = rest.split_at(self.b1.data().len());
+        let (w2, b2) = rest.split_at(self.w2.data().len());
+
+        let (hidden, input) = self.w1.shape();
+        self.w1 = Tensor::new(hidden, input, w1.to_vec());
+        self.b1 = Tensor::column(b1.to_vec());
+        self.w2 = Tensor::new(self.w2.shape().0, hidden, w2.to_vec());
+        self.b2 = Tensor::column(b2.to_vec());
+    }
+
+    fn accumulate_gradient(&self, example: &[Fixed], loss: &impl Loss, grads: &mut [Fixed]) {
+        let (input, output) = (self.w1.shape().1, self.w2.shape().0);
+
+        // Get x (input data) and y (one-hot encoded label)
+        let x = Tensor::column(example[..input].to_vec());
+        let y = &example[input..input + output];
+
+        // Forward pass
+        let (z1, h, yhat) = mlp_forward(&self.w1, &self.b1, &self.w2, &self.b2, &x);
+
+        // Backward pass from the gradient of the loss with respect to the output
+        let grad_out = Tensor::column(loss.gradient(yhat.data(), y));
+        let back = self.w2.transpose().matmul(&grad_out);
+        let gate = z1.map(|z| activation::Relu.derivative(z));
+
+        let dw1 = gate.matmul(&x.transpose()).mul(&back);
+        let db1 = gate.mul(&back);
+        let dw2 = grad_out.matmul(&h.transpose());
+        let db2 = grad_out;
+
+        let flat = [dw1.data(), db1.data(), dw2.data(), db2.data()].concat();
+        for (g, d) in grads.iter_mut().zip(flat) {
+            *g += d;
+        }
+    }
+}
+
+// The `loss` and accuracy of the MLP on raw examples holding the features followed by the
+// one-hot label.
+pub fn evaluate_mlp_on(mlp: &MLP, rows: &[Vec<u32>], loss: LossKind) -> Metrics<Fixed> {
+    let input = mlp.input_dim();
+    let (w1, b1) = (mlp.w1.map(Fixed::from_raw), mlp.b1.map(Fixed::from_raw));
+    let (w2, b2) = (mlp.w2.map(Fixed::from_raw), mlp.b2.map(Fixed::from_raw));
+
+    Metrics::score(&loss, decode_examples(rows, input + mlp.output_dim()).into_iter().map(|example| {
+        let x = Tensor::column(example[..input].to_vec());
+        (mlp_forward(&w1, &b1, &w2, &b2, &x).2.into_data(), example[input..].to_vec())
+    }))
+}
+
+// Run a model on a single raw input and return its raw outputs. `model` is the guest mode that
+// trains that model, and `architecture` and `params` are laid out as in its training public values.
+// The forward passes are the ones used in training, so a prediction always matches the trained
+// model. A model trained with a cross-entropy predicts logits.
+pub fn predict(model: GuestMode, architecture: &[u32], params: &[u32], input: &[u32]) -> Vec<u32> {
+    let input = Tensor::column(input.to_vec()).map(Fixed::from_raw);
+
+    let output = match model {
+        GuestMode::Perceptron => {
+            let [w_l, w_r, b] = params[..] else { panic!("a perceptron has two weights and a bias") };
+            let [left, right] = input.data()[..] else { panic!("a perceptron takes two inputs") };
+            let [w_l, w_r, b] = [w_l, w_r, b].map(Fixed::from_raw);
+            let predicted = perceptron_forward(w_l, w_r, b, left, right);
+            Tensor::column(alloc::vec![predicted])
+        }
+        GuestMode::Sequential => {
+            let specs = LayerSpec::decode_all(architecture).expect("invalid layer specs");
+            let params: Vec<Fixed> = params.iter().copied().map(Fixed::from_raw).collect();
+            let model = Sequential::from_params(&specs, &params);
+            model.forward(&input).output().clone()
+        }
+        GuestMode::Mlp => {
+            let shape: [u32; 3] = architecture.try_into().expect("MLP shape has three widths");
+            let mlp = MLP::from_params(shape, params);
+            let (w1, b1) = (mlp.w1.map(Fixed::from_raw), mlp.b1.map(Fixed::from_raw));
+            let (w2, b2) = (mlp.w2.map(Fixed::from_raw), mlp.b2.map(Fixed::from_raw));
+            mlp_forward(&w1, &b1, &w2, &b2, &input).2
+        }
+        GuestMode::Infer => panic!("inference is not a model"),
+    };
+
+    output.into_data().into_iter().map(Fixed::to_raw).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parameters of the 4-4-3 MLP with mixed signs, so that some hidden units are active.
+    fn mlp_test_params() -> Vec<u32> {
+        (0..35).map(|i| Fixed::from_f32(((i * 7) % 11) as f32 / 20.0 - 0.2).to_raw()).collect()
+    }
+
+    // Per-example SGD on the squared error of a 4-4-3 MLP, written out weight by weight: every dot
+    // product is summed from the left starting with its first term, and the bias is added last.
+    // This is the current update rule, gradient descent through a step that is one for positive
+    // inputs. It is not the original unrolled `update_mlp` that `Tensor` replaced, which ascended
+    // the gradient through an inverted step, so it checks the tensor code against the rule the
+    // guest proves today, not against that original.
+    fn update_mlp_descent_reference(params: &[u32], rows: &[Vec<u32>], num_epochs: u32, eta: u32) -> Vec<u32> {
+        let p: Vec<Fixed> = params.iter().copied().map(Fixed::from_raw).collect();
+        let mut w1: [[Fixed; 4]; 4] = core::array::from_fn(|i| core::array::from_fn(|j| p[4 * i + j]));
+        let mut b1: [Fixed; 4] = core::array::from_fn(|i| p[16 + i]);
+        let mut w2: [[Fixed; 4]; 3] = core::array::from_fn(|o| core::array::from_fn(|i| p[20 + 4 * o + i]));
+        let mut b2: [Fixed; 3] = core::array::from_fn(|o| p[32 + o]);
+
+        let lr = Fixed::from_raw(eta);
+        let neg_two = Fixed::from_f32(-2.0);
+        for _ in 0..num_epochs {
+            for row in decode_rows::<7>(rows) {
+                let (x, y) = (&row[..4], &row[4..]);
+
+                let z1: [Fixed; 4] = core::array::from_fn(|i| {
+                    w1[i][0] * x[0] + w1[i][1] * x[1] + w1[i][2] * x[2] + w1[i][3] * x[3] + b1[i]
+                });
+                let h = z1.map(relu);
+                let yhat: [Fixed; 3] = core::array::from_fn(|o| {
+                    w2[o][0] * h[0] + w2[o][1] * h[1] + w2[o][2] * h[2] + w2[o][3] * h[3] + b2[o]
+                });
+
+                let grad_out: [Fixed; 3] = core::array::from_fn(|o| neg_two * (y[o] - yhat[o]));
+                let back: [Fixed; 4] =
+                    core::array::from_fn(|i| w2[0][i] * grad_out[0] + w2[1][i] * grad_out[1] + w2[2][i] * grad_out[2]);
+                let gate = z1.map(heaviside);
+
+                // The gradients are accumulated from zero before the step, as `TrainingState::train` does.
+                let step = |p: Fixed, g: Fixed| p - lr * (Fixed::ZERO + g);
+                for (i, (w1_i, b1_i)) in w1.iter_mut().zip(&mut b1).enumerate() {
+                    for (w, &x_j) in w1_i.iter_mut().zip(x) {
+                        *w = step(*w, gate[i] * x_j * back[i]);
+                    }
+                    *b1_i = step(*b1_i, gate[i] * back[i]);
+                }
+                for (o, (w2_o, b2_o)) in w2.iter_mut().zip(&mut b2).enumerate() {
+                    for (w, &h_i) in w2_o.iter_mut().zip(&h) {
+                        *w = step(*w, grad_out[o] * h_i);
+                    }
+                    *b2_o = step(*b2_o, grad_out[o]);
+                }
+            }
+        }
+
+        [w1.concat(), b1.to_vec(), w2.concat(), b2.to_vec()].concat().into_iter().map(Fixed::to_raw).collect()
+    }
+
+    // The parameters after three epochs of the current rule on `MlpTest` with a learning rate of
+    // 0.01.
+    #[cfg(not(feature = "i16f16"))]
+    #[rustfmt::skip]
+    const MLP_TEST_GOLDEN: [u32; 35] = [
+        0xbe4ec0be, 0x3e15b1b8, 0xbd643c12, 0x3e95b1b9,
+        0x3dcc3b9e, 0xbdcdef2e, 0x3e7f2636, 0x3d484347,
+        0xbe19999a, 0x3e4ccccd, 0x00000000, 0xbe4ccccd,
+        0x3e17de3c, 0xbd5aa7b0, 0x3e97008f, 0x3dbef1ea,
+        0xbdd0b4ae, 0x3e7fb766, 0x3d4ccccc, 0xbe1b54f7,
+        0x3e7049f0, 0x3d177b77, 0xbe4ccccd, 0x3e425bdb,
+        0xbd436174, 0x3e9adb05, 0x3dccccce, 0xbdc7645a,
+        0x3e585391, 0x3c0e2299, 0xbe19999a, 0x3e1f3d9a,
+        0x3d0ae88f, 0xbe4a7f7a, 0x3de59b58,
+    ];
+
+    #[cfg(feature = "i16f16")]
+    #[rustfmt::skip]
+    const MLP_TEST_GOLDEN: [u32; 35] = [
+        0xffffcc52, 0x0000256e, 0xfffff1be, 0x00004adb,
+        0x00001989, 0xffffe643, 0x00003fcb, 0x00000c86,
+        0xffffd99a, 0x00003333, 0x00000000, 0xffffcccd,
+        0x000025f9, 0xfffff257, 0x00004b82, 0x000017e0,
+        0xffffe5eb, 0x00003fef, 0x00000ccd, 0xffffd92d,
+        0x00003c13, 0x00000979, 0xffffcccd, 0x00003096,
+        0xfffff3cb, 0x00004d6f, 0x0000199a, 0xffffe714,
+        0x00003617, 0x0000023b, 0xffffd99a, 0x000027d2,
+        0x000008af, 0xffffcd62, 0x00001cb6,
+    ];
+
+    #[test]
+    fn update_mlp_matches_unrolled_descent_reference() {
+        let (params, eta) = (mlp_test_params(), Fixed::from_f32(0.01).to_raw());
+        let trained = update_mlp(MLP::from_params([4, 4, 3], &params), DatasetId::MlpTest, 3, eta);
+
+        assert_eq!(trained.params(), update_mlp_descent_reference(&params, &DatasetId::MlpTest.rows(), 3, eta));
+        assert_eq!(trained.params(), MLP_TEST_GOLDEN);
+    }
+
+    // The public values of a private perceptron run of `t` epochs on AND from `checkpoint`, and
+    // the checkpoint it ends with.
+    fn segment(checkpoint: &Checkpoint, t: u32) -> (DigestPublicValuesStruct, Checkpoint) {
+        let (rows, r) = (DatasetId::And.rows(), Fixed::from_f32(0.1).to_raw());
+        let updated = update_checkpoint(checkpoint, &rows, t, r);
+        let [w_l, w_r, b] = updated.params()[..] else { unreachable!() };
+        let root = MerkleTree::new(&rows).root();
+        let evaluation = EvaluationStruct::new(root, LossKind::Mse, evaluate_perceptron_on(&rows, w_l, w_r, b));
+
+        (DigestPublicValuesStruct::new(root, t, r, checkpoint, &updated, evaluation), updated)
+    }
+
+    // Split a run of `t` epochs into segments of at most `k` epochs.
+    fn segments(checkpoint: &Checkpoint, t: u32, k: u32) -> Vec<DigestPublicValuesStruct> {
+        let (mut checkpoint, mut epochs, mut segments) = (checkpoint.clone(), 0, Vec::new());
+        while epochs < t {
+            let (public_values, updated) = segment(&checkpoint, k.min(t - epochs));
+            epochs += public_values.t;
+            segments.push(public_values);
+            checkpoint = updated;
+        }
+        segments
+    }
+
+    #[test]
+    fn chained_segments_match_a_single_run() {
+        let raw = |v: f32| Fixed::from_f32(v).to_raw();
+        let start = Checkpoint::perceptron(raw(0.3), raw(-0.2), raw(0.1));
+        let (single, _) = segment(&start, 6);
+        let vkey = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        for k in [6, 3, 2] {
+            let segments = segments(&start, 6, k);
+            assert_eq!(segments.len(), (6 / k) as usize);
+
+            let aggregate = AggregatePublicValuesStruct::chain(vkey, &segments).unwrap();
+            assert_eq!(aggregate.t, 6);
+            assert_eq!((aggregate.mode, aggregate.r), (single.mode, single.r));
+            assert_eq!(aggregate.dataset_root, single.dataset_root);
+            assert_eq!(aggregate.weights_digest, single.weights_digest, "{} segments", segments.len());
+            assert_eq!(aggregate.updated_weights_digest, single.updated_weights_digest, "{} segments", segments.len());
+            assert_eq!(aggregate.evaluation, single.evaluation);
+            assert_eq!(aggregate.vkey[..4], [0, 0, 0, 1]);
         }
     }
 
-    MLP{
-        w1_11: w1_11.to_bits(),
-        w1_12: w1_12.to_bits(),
-        w1_13: w1_13.to_bits(),
-        w1_14: w1_14.to_bits(),
-        w1_21: w1_21.to_bits(),
-        w1_22: w1_22.to_bits(),
-        w1_23: w1_23.to_bits(),
-        w1_24: w1_24.to_bits(),
-        w1_31: w1_31.to_bits(),
-        w1_32: w1_32.to_bits(),
-        w1_33: w1_33.to_bits(),
-        w1_34: w1_34.to_bits(),
-        w1_41: w1_41.to_bits(),
-        w1_42: w1_42.to_bits(),
-        w1_43: w1_43.to_bits(),
-        w1_44: w1_44.to_bits(),
-        b1_1: b1_1.to_bits(),
-        b1_2: b1_2.to_bits(),
-        b1_3: b1_3.to_bits(),
-        b1_4: b1_4.to_bits(),
-        w2_11: w2_11.to_bits(),
-        w2_12: w2_12.to_bits(),
-        w2_13: w2_13.to_bits(),
-        w2_14: w2_14.to_bits(),
-        w2_21: w2_21.to_bits(),
-        w2_22: w2_22.to_bits(),
-        w2_23: w2_23.to_bits(),
-        w2_24: w2_24.to_bits(),
-        w2_31: w2_31.to_bits(),
-        w2_32: w2_32.to_bits(),
-        w2_33: w2_33.to_bits(),
-        w2_34: w2_34.to_bits(),
-        b2_1: b2_1.to_bits(),
-        b2_2: b2_2.to_bits(),
-        b2_3: b2_3.to_bits(),
-    }
-}
\ No newline at end of file
+    #[test]
+    fn chain_rejects_broken_segments() {
+        let raw = |v: f32| Fixed::from_f32(v).to_raw();
+        let segments = segments(&Checkpoint::perceptron(raw(0.3), raw(-0.2), raw(0.1)), 6, 2);
+        let vkey = [0; 8];
+
+        assert_eq!(AggregatePublicValuesStruct::chain(vkey, &[]).err(), Some(ChainError::Empty));
+
+        let swapped = [segments[0].clone(), segments[2].clone(), segments[1].clone()];
+        assert_eq!(AggregatePublicValuesStruct::chain(vkey, &swapped).err(), Some(ChainError::BrokenLink(1)));
+
+        let mut skipped = segments.clone();
+        skipped.remove(1);
+        assert_eq!(AggregatePublicValuesStruct::chain(vkey, &skipped).err(), Some(ChainError::BrokenLink(1)));
+
+        let mut tampered = segments.clone();
+        tampered[2].weights_digest.0[0] ^= 1;
+        assert_eq!(AggregatePublicValuesStruct::chain(vkey, &tampered).err(), Some(ChainError::BrokenLink(2)));
+
+        let mut other = segments.clone();
+        other[1].r += 1;
+        assert_eq!(AggregatePublicValuesStruct::chain(vkey, &other).err(), Some(ChainError::WrongLearningRate(1)));
+
+        let mut other = segments.clone();
+        other[2].dataset_root.0[0] ^= 1;
+        assert_eq!(AggregatePublicValuesStruct::chain(vkey, &other).err(), Some(ChainError::WrongDataset(2)));
+
+        let mut other = segments.clone();
+        other[1].mode = GuestMode::Mlp.to_u32();
+        assert_eq!(AggregatePublicValuesStruct::chain(vkey, &other).err(), Some(ChainError::WrongModel(1)));
+
+        let mut long = segments;
+        long[0].t = u32::MAX;
+        assert_eq!(AggregatePublicValuesStruct::chain(vkey, &long).err(), Some(ChainError::TooManyEpochs));
+    }
+}
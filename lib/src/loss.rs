@@ -0,0 +1,328 @@
+//! Loss functions, and the exponential and logarithm they are built from.
+//!
+//! Training minimises a [`Loss`] of the model outputs against the targets of every example. [`Mse`]
+//! is the squared error `sum((y - yhat)^2)`, which suits regression and the perceptron. The two
+//! cross-entropies suit classifiers and read the outputs as logits, so the last layer of a
+//! classifier should have the identity activation: [`BinaryCrossEntropy`] applies a sigmoid to each
+//! output on its own, [`SoftmaxCrossEntropy`] a softmax over all outputs, for one-hot labels.
+//!
+//! Both cross-entropies are written in the stable forms `max(z, 0) - z y + ln(1 + e^-|z|)` and
+//! `ln(sum(e^(z_j - max z))) + max z - z_i`, which only take the exponential of non-positive
+//! numbers, so it never overflows in `f32` or saturates in fixed point, however large the logits
+//! are. Their gradients with respect to the logits are `sigmoid(z) - y` and `softmax(z) - y`.
+//!
+//! [`exp`] and [`ln`] are computed with the operations of [`Scalar`] only, by range reduction and
+//! a Taylor series, so every backend gives the same bits on the host and in the guest.
+//!
+//! Each gradient agrees with a central finite difference of its loss, even for large logits:
+//!
+//! ```
+//! use fibonacci_lib::loss::{BinaryCrossEntropy, Loss, Mse, SoftmaxCrossEntropy};
+//!
+//! fn check(loss: impl Loss, output: [f32; 3], target: [f32; 3]) {
+//!     let h = 1e-2f32;
+//!     let gradient = loss.gradient(&output, &target);
+//!     for i in 0..3 {
+//!         let (mut above, mut below) = (output, output);
+//!         above[i] += h;
+//!         below[i] -= h;
+//!         let finite_difference = (loss.loss(&above, &target) - loss.loss(&below, &target)) / (2.0 * h);
+//!         assert!((finite_difference - gradient[i]).abs() < 1e-2 * gradient[i].abs().max(1.0), "at {}", i);
+//!     }
+//! }
+//!
+//! for output in [[0.5, -1.0, 2.0], [30.0, -40.0, 0.25], [100.0, 99.0, -100.0]] {
+//!     check(Mse, output, [0.0, 1.0, 0.0]);
+//!     check(BinaryCrossEntropy, output, [0.0, 1.0, 0.0]);
+//!     check(SoftmaxCrossEntropy, output, [0.0, 1.0, 0.0]);
+//! }
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::Scalar;
+
+// Below `-EXP_CUTOFF`, `e^x` is taken to be zero. `e^-24` is below `2^-34`, so it is lost next to
+// any term of order one, and it already rounds to zero in 16.16 fixed point.
+const EXP_CUTOFF: f32 = 24.0;
+
+/// The loss of a model on one example.
+pub trait Loss {
+    /// The loss of the model outputs against the targets, summed over the outputs.
+    fn loss<T: Scalar>(&self, output: &[T], target: &[T]) -> T;
+
+    /// The gradient of [`Loss::loss`] with respect to the outputs.
+    fn gradient<T: Scalar>(&self, output: &[T], target: &[T]) -> Vec<T>;
+
+    /// What the outputs predict: the outputs themselves, or probabilities for the cross-entropies.
+    fn predict<T: Scalar>(&self, output: &[T]) -> Vec<T>;
+}
+
+/// The squared error `sum((y - yhat)^2)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mse;
+
+/// The cross-entropy of `sigmoid(z)` against `y`, for every output `z` and target `y` in `[0, 1]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BinaryCrossEntropy;
+
+/// The cross-entropy of `softmax(z)` against a target distribution such as a one-hot label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoftmaxCrossEntropy;
+
+impl Loss for Mse {
+    fn loss<T: Scalar>(&self, output: &[T], target: &[T]) -> T {
+        let mut sum = T::ZERO;
+        for (&o, &y) in output.iter().zip(target) {
+            sum = sum + (y - o) * (y - o);
+        }
+        sum
+    }
+
+    fn gradient<T: Scalar>(&self, output: &[T], target: &[T]) -> Vec<T> {
+        let neg_two = T::from_f32(-2.0);
+        output.iter().zip(target).map(|(&o, &y)| neg_two * (y - o)).collect()
+    }
+
+    fn predict<T: Scalar>(&self, output: &[T]) -> Vec<T> {
+        output.to_vec()
+    }
+}
+
+impl Loss for BinaryCrossEntropy {
+    fn loss<T: Scalar>(&self, output: &[T], target: &[T]) -> T {
+        let mut sum = T::ZERO;
+        for (&z, &y) in output.iter().zip(target) {
+            let (positive, abs) = if T::ZERO < z { (z, z) } else { (T::ZERO, -z) };
+            sum = sum + (positive - z * y + ln(T::ONE + exp(-abs)));
+        }
+        sum
+    }
+
+    fn gradient<T: Scalar>(&self, output: &[T], target: &[T]) -> Vec<T> {
+        output.iter().zip(target).map(|(&z, &y)| sigmoid(z) - y).collect()
+    }
+
+    fn predict<T: Scalar>(&self, output: &[T]) -> Vec<T> {
+        output.iter().copied().map(sigmoid).collect()
+    }
+}
+
+impl Loss for SoftmaxCrossEntropy {
+    fn loss<T: Scalar>(&self, output: &[T], target: &[T]) -> T {
+        let max = max(output);
+        let log_sum = ln(output.iter().fold(T::ZERO, |sum, &z| sum + exp(z - max)));
+
+        let mut sum = T::ZERO;
+        for (&z, &y) in output.iter().zip(target) {
+            sum = sum + y * (log_sum + (max - z));
+        }
+        sum
+    }
+
+    fn gradient<T: Scalar>(&self, output: &[T], target: &[T]) -> Vec<T> {
+        // For targets that do not sum to one, the softmax is weighted by their total.
+        let total = target.iter().fold(T::ZERO, |sum, &y| sum + y);
+        self.predict(output).into_iter().zip(target).map(|(p, &y)| p * total - y).collect()
+    }
+
+    fn predict<T: Scalar>(&self, output: &[T]) -> Vec<T> {
+        let max = max(output);
+        let exps: Vec<T> = output.iter().map(|&z| exp(z - max)).collect();
+        let sum = exps.iter().fold(T::ZERO, |sum, &e| sum + e);
+
+        exps.into_iter().map(|e| e / sum).collect()
+    }
+}
+
+/// The loss of a training run, encoded as a `u32` in the training state and the public values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LossKind {
+    Mse,
+    BinaryCrossEntropy,
+    SoftmaxCrossEntropy,
+}
+
+impl LossKind {
+    pub fn to_u32(self) -> u32 {
+        match self {
+            LossKind::Mse => 0,
+            LossKind::BinaryCrossEntropy => 1,
+            LossKind::SoftmaxCrossEntropy => 2,
+        }
+    }
+
+    pub fn from_u32(i: u32) -> Option<Self> {
+        match i {
+            0 => Some(LossKind::Mse),
+            1 => Some(LossKind::BinaryCrossEntropy),
+            2 => Some(LossKind::SoftmaxCrossEntropy),
+            _ => None,
+        }
+    }
+}
+
+impl Loss for LossKind {
+    fn loss<T: Scalar>(&self, output: &[T], target: &[T]) -> T {
+        match self {
+            LossKind::Mse => Mse.loss(output, target),
+            LossKind::BinaryCrossEntropy => BinaryCrossEntropy.loss(output, target),
+            LossKind::SoftmaxCrossEntropy => SoftmaxCrossEntropy.loss(output, target),
+        }
+    }
+
+    fn gradient<T: Scalar>(&self, output: &[T], target: &[T]) -> Vec<T> {
+        match self {
+            LossKind::Mse => Mse.gradient(output, target),
+            LossKind::BinaryCrossEntropy => BinaryCrossEntropy.gradient(output, target),
+            LossKind::SoftmaxCrossEntropy => SoftmaxCrossEntropy.gradient(output, target),
+        }
+    }
+
+    fn predict<T: Scalar>(&self, output: &[T]) -> Vec<T> {
+        match self {
+            LossKind::Mse => Mse.predict(output),
+            LossKind::BinaryCrossEntropy => BinaryCrossEntropy.predict(output),
+            LossKind::SoftmaxCrossEntropy => SoftmaxCrossEntropy.predict(output),
+        }
+    }
+}
+
+/// `1 / (1 + e^-z)`, taking the exponential of `-|z|` only.
+pub fn sigmoid<T: Scalar>(z: T) -> T {
+    if T::ZERO < z {
+        T::ONE / (T::ONE + exp(-z))
+    } else {
+        let e = exp(z);
+        e / (T::ONE + e)
+    }
+}
+
+/// `e^x` for `x <= 0`, as `e^-n e^r` with a whole number `n` and `r` in `[-1, 0]`, where `e^r` is
+/// a Taylor polynomial of degree ten. In `f32` the relative error is below `1e-6`, `e^-inf` is zero
+/// and NaN is returned as is. Panics on positive input.
+pub fn exp<T: Scalar>(x: T) -> T {
+    if is_nan(x) {
+        return x;
+    }
+    assert!(x <= T::ZERO, "exponential of a positive number");
+    if x < T::from_f32(-EXP_CUTOFF) {
+        return T::ZERO;
+    }
+
+    let e_inv = T::from_f32(1.0 / core::f32::consts::E);
+    let (mut r, mut scale) = (x, T::ONE);
+    while r < -T::ONE {
+        r = r + T::ONE;
+        scale = scale * e_inv;
+    }
+
+    // Horner's scheme for `1 + r + r^2/2! + ... + r^10/10!`.
+    let mut result = T::ONE;
+    for k in (1..=10).rev() {
+        result = T::ONE + r * result / T::from_f32(k as f32);
+    }
+    scale * result
+}
+
+/// The natural logarithm of positive `x`, as `k ln(2) + ln(m)` with `m` in `[1, 2)`, where
+/// `ln(m) = 2 atanh((m - 1) / (m + 1))` is summed up to the 15th power. In `f32`, infinity and
+/// NaN are returned as is. Panics on non-positive input.
+pub fn ln<T: Scalar>(x: T) -> T {
+    if is_nan(x) {
+        return x;
+    }
+    assert!(T::ZERO < x, "logarithm of a non-positive number");
+    // Only `f32` has values above its largest finite one, and halving infinity would never end.
+    if T::from_f32(f32::MAX) < x {
+        return x;
+    }
+    let (two, half) = (T::from_f32(2.0), T::from_f32(0.5));
+
+    let (mut m, mut k) = (x, T::ZERO);
+    while two <= m {
+        m = m * half;
+        k = k + T::ONE;
+    }
+    while m < T::ONE {
+        m = m * two;
+        k = k - T::ONE;
+    }
+
+    // Horner's scheme in `u^2` for `u + u^3/3 + ... + u^15/15`, with `u` in `[0, 1/3)`.
+    let u = (m - T::ONE) / (m + T::ONE);
+    let u2 = u * u;
+    let mut series = T::ZERO;
+    for k in (0..8).rev() {
+        series = T::from_f32(1.0 / (2 * k + 1) as f32) + u2 * series;
+    }
+    k * T::from_f32(core::f32::consts::LN_2) + two * u * series
+}
+
+// Whether `x` is NaN, the only value that is not comparable to itself.
+fn is_nan<T: Scalar>(x: T) -> bool {
+    x.partial_cmp(&x).is_none()
+}
+
+// The largest output. Outputs are never empty, since every layer has at least one unit.
+fn max<T: Scalar>(values: &[T]) -> T {
+    values.iter().copied().fold(values[0], |max, v| if max < v { v } else { max })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numeric::Q16;
+    use alloc::vec;
+
+    #[test]
+    fn exp_and_ln_of_non_finite_values() {
+        assert_eq!(exp(f32::NEG_INFINITY), 0.0);
+        assert!(exp(f32::NAN).is_nan());
+        assert_eq!(ln(f32::INFINITY), f32::INFINITY);
+        assert!(ln(f32::NAN).is_nan());
+        assert_eq!(ln(f32::MAX), 88.72284);
+    }
+
+    #[test]
+    fn exp_and_ln_saturate_in_fixed_point() {
+        let (min, max) = (Q16::from_raw(i32::MIN as u32), Q16::from_raw(i32::MAX as u32));
+        assert_eq!(exp(min), Q16::ZERO);
+        assert!((ln(max).to_f32() - 32768f32.ln()).abs() < 1e-3);
+        assert!((ln(Q16::from_raw(1)).to_f32() - (-16.0 * core::f32::consts::LN_2)).abs() < 1e-3);
+    }
+
+    // Logits far beyond the range where the exponential is exact give the limits of the losses,
+    // with zero for the correct class and saturation in fixed point for the others.
+    fn check_extreme_logits<T: Scalar>() {
+        let (zero, one, big) = (T::ZERO, T::ONE, T::from_f32(1e6));
+
+        for (z, y, loss) in [(big, one, zero), (big, zero, big), (-big, zero, zero), (-big, one, big)] {
+            assert_eq!(BinaryCrossEntropy.loss(&[z], &[y]), loss, "z = {:?}, y = {:?}", z, y);
+            let expected = if T::ZERO < z { one - y } else { -y };
+            assert_eq!(BinaryCrossEntropy.gradient(&[z], &[y]), vec![expected], "z = {:?}, y = {:?}", z, y);
+        }
+
+        let logits = [big, -big, zero];
+        assert_eq!(SoftmaxCrossEntropy.loss(&logits, &[one, zero, zero]), zero);
+        assert_eq!(SoftmaxCrossEntropy.loss(&logits, &[zero, one, zero]), big + big);
+        assert_eq!(SoftmaxCrossEntropy.loss(&logits, &[zero, zero, one]), big);
+        assert_eq!(SoftmaxCrossEntropy.predict(&logits), vec![one, zero, zero]);
+        assert_eq!(SoftmaxCrossEntropy.gradient(&logits, &[zero, one, zero]), vec![one, -one, zero]);
+
+        let half = T::from_f32(0.5);
+        assert_eq!(SoftmaxCrossEntropy.predict(&[big, big, -big]), vec![half, half, zero]);
+        let tie = SoftmaxCrossEntropy.loss(&[big, big], &[one, zero]).to_f32();
+        assert!((tie - core::f32::consts::LN_2).abs() < 1e-4, "{}", tie);
+    }
+
+    #[test]
+    fn extreme_logits_f32() {
+        check_extreme_logits::<f32>();
+    }
+
+    #[test]
+    fn extreme_logits_q16() {
+        check_extreme_logits::<Q16>();
+    }
+}
@@ -0,0 +1,229 @@
+//! Merkle commitments to datasets.
+//!
+//! Each example is serialized as its raw `u32` words and hashed into a leaf. Leaves and inner
+//! nodes are domain-separated (`0x00` and `0x01` prefixes) so a node can never be passed off as
+//! an example. When a level has an odd number of nodes, the last one is promoted unchanged to
+//! the next level. The root of an empty dataset is all zeros.
+//!
+//! The root commits to the examples and their order, so a proof whose public values contain a
+//! root is tied to exactly that dataset. An [`InclusionProof`] shows that a single example is
+//! part of a committed dataset without revealing the others.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+pub fn leaf_hash(example: &[u32]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    for word in example {
+        hasher.update(word.to_le_bytes());
+    }
+
+    hasher.finalize().into()
+}
+
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+
+    hasher.finalize().into()
+}
+
+/// A Merkle tree over the examples of a dataset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree {
+    // `levels[0]` holds the leaves, the last level holds the root.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new<R: AsRef<[u32]>>(examples: &[R]) -> Self {
+        let mut levels = vec![examples.iter().map(|e| leaf_hash(e.as_ref())).collect::<Vec<_>>()];
+
+        while levels[levels.len() - 1].len() > 1 {
+            let next = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Self { levels }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.levels[self.levels.len() - 1].first().copied().unwrap_or([0; 32])
+    }
+
+    pub fn num_leaves(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Prove that the example at `index` is part of the tree.
+    pub fn inclusion_proof(&self, index: usize) -> InclusionProof {
+        assert!(index < self.num_leaves(), "example {} out of range", index);
+
+        let mut siblings = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if sibling < level.len() {
+                siblings.push(level[sibling]);
+            }
+            position /= 2;
+        }
+
+        InclusionProof { index, num_leaves: self.num_leaves(), siblings }
+    }
+}
+
+/// The sibling hashes on the path from one example to the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub index: usize,
+    pub num_leaves: usize,
+    pub siblings: Vec<Hash>,
+}
+
+impl InclusionProof {
+    /// Check that `example` sits at `self.index` in the dataset committed to by `root`.
+    pub fn verify(&self, root: &Hash, example: &[u32]) -> bool {
+        if self.index >= self.num_leaves {
+            return false;
+        }
+
+        let mut hash = leaf_hash(example);
+        let mut position = self.index;
+        let mut width = self.num_leaves;
+        let mut siblings = self.siblings.iter();
+
+        while width > 1 {
+            // A node without a sibling is promoted to the next level unchanged.
+            if position ^ 1 < width {
+                let Some(sibling) = siblings.next() else {
+                    return false;
+                };
+                hash = if position % 2 == 0 { node_hash(&hash, sibling) } else { node_hash(sibling, &hash) };
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        siblings.next().is_none() && hash == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn examples(n: u32) -> Vec<Vec<u32>> {
+        (0..n).map(|i| vec![i, 2 * i + 1, 0xdead_beef ^ i]).collect()
+    }
+
+    #[test]
+    fn empty_root_is_zero() {
+        assert_eq!(MerkleTree::new::<Vec<u32>>(&[]).root(), [0; 32]);
+    }
+
+    #[test]
+    fn single_leaf() {
+        let rows = examples(1);
+        let tree = MerkleTree::new(&rows);
+        assert_eq!(tree.root(), leaf_hash(&rows[0]));
+
+        let proof = tree.inclusion_proof(0);
+        assert!(proof.siblings.is_empty());
+        assert!(proof.verify(&tree.root(), &rows[0]));
+        assert!(!proof.verify(&tree.root(), &[1, 2, 3]));
+    }
+
+    #[test]
+    fn odd_leaf_is_promoted() {
+        let rows = examples(3);
+        let [a, b, c] = [0, 1, 2].map(|i| leaf_hash(&rows[i]));
+        assert_eq!(MerkleTree::new(&rows).root(), node_hash(&node_hash(&a, &b), &c));
+
+        let rows = examples(5);
+        let [a, b, c, d, e] = [0, 1, 2, 3, 4].map(|i| leaf_hash(&rows[i]));
+        let expected = node_hash(&node_hash(&node_hash(&a, &b), &node_hash(&c, &d)), &e);
+        assert_eq!(MerkleTree::new(&rows).root(), expected);
+    }
+
+    #[test]
+    fn verifies_every_index() {
+        for n in 1..=17 {
+            let rows = examples(n);
+            let tree = MerkleTree::new(&rows);
+            assert_eq!(tree.num_leaves(), n as usize);
+            for (i, row) in rows.iter().enumerate() {
+                let proof = tree.inclusion_proof(i);
+                assert!(proof.verify(&tree.root(), row), "leaf {} of {}", i, n);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_proofs() {
+        let rows = examples(7);
+        let tree = MerkleTree::new(&rows);
+        let root = tree.root();
+
+        for (i, row) in rows.iter().enumerate() {
+            let proof = tree.inclusion_proof(i);
+
+            // Another example, at this index or at another one.
+            let other = &rows[(i + 1) % rows.len()];
+            assert!(!proof.verify(&root, other), "leaf {}", i);
+            assert!(!InclusionProof { index: (i + 1) % rows.len(), ..proof.clone() }.verify(&root, row));
+
+            // A flipped bit in any sibling.
+            for s in 0..proof.siblings.len() {
+                let mut tampered = proof.clone();
+                tampered.siblings[s][0] ^= 1;
+                assert!(!tampered.verify(&root, row), "leaf {} sibling {}", i, s);
+            }
+
+            // A missing or an extra sibling.
+            let mut short = proof.clone();
+            short.siblings.pop();
+            assert!(!short.verify(&root, row));
+            let mut long = proof.clone();
+            long.siblings.push(root);
+            assert!(!long.verify(&root, row));
+
+            // An index past the end.
+            assert!(!InclusionProof { index: 7, ..proof.clone() }.verify(&root, row));
+        }
+    }
+
+    #[test]
+    fn leaves_and_nodes_are_separated() {
+        let rows = examples(2);
+        let tree = MerkleTree::new(&rows);
+
+        // The words of two leaf hashes, read as an example, do not hash to their parent.
+        let words: Vec<u32> = [leaf_hash(&rows[0]), leaf_hash(&rows[1])]
+            .concat()
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_ne!(leaf_hash(&words), tree.root());
+    }
+
+    #[test]
+    #[should_panic(expected = "example 3 out of range")]
+    fn inclusion_proof_rejects_out_of_range_index() {
+        MerkleTree::new(&examples(3)).inclusion_proof(3);
+    }
+}
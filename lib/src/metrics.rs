@@ -0,0 +1,132 @@
+//! Loss and accuracy of a trained model.
+//!
+//! After training, the guest runs the trained model over an evaluation set, either the training
+//! set or a held-out set, and commits the metrics next to the Merkle root of that set. The loss is
+//! the [`Loss`] that training minimises, averaged over the examples. Accuracy is committed as
+//! integer counts, so a claim like "at least 95% accuracy" can be checked exactly as
+//! `100 * correct >= 95 * total`.
+//!
+//! Accuracy is scored on what the outputs predict according to the loss, that is on the outputs
+//! themselves for the squared error and on probabilities for the cross-entropies. A model with a
+//! single output is correct when the prediction and the target lie on the same side of one half. A
+//! model with several outputs is correct when its largest prediction is at the index of the largest
+//! target, which is the class of a one-hot label.
+//!
+//! ```
+//! use fibonacci_lib::loss::Mse;
+//! use fibonacci_lib::metrics::Metrics;
+//!
+//! let outputs = [[0.9f32, 0.1], [0.2, 0.6], [0.7, 0.4]];
+//! let labels = [[1.0f32, 0.0], [0.0, 1.0], [0.0, 1.0]];
+//! let metrics = Metrics::score(&Mse, outputs.iter().zip(&labels));
+//!
+//! assert_eq!((metrics.correct, metrics.total), (2, 3));
+//! assert!((metrics.loss - 1.07 / 3.0).abs() < 1e-6);
+//! ```
+
+use crate::loss::Loss;
+use crate::Scalar;
+
+/// The loss and accuracy of a model on an evaluation set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metrics<T> {
+    /// The mean loss per example.
+    pub loss: T,
+    /// The number of correctly predicted examples.
+    pub correct: u32,
+    /// The number of examples.
+    pub total: u32,
+}
+
+impl<T: Scalar> Metrics<T> {
+    /// Score the outputs of a model against their targets with `loss`, one pair per example.
+    ///
+    /// The loss is accumulated in `T`, so on the fixed-point backends it saturates like any other
+    /// value. The loss of an empty evaluation set is zero.
+    ///
+    /// Panics if `T` cannot hold the number of examples, as [`Q16`](crate::numeric::Q16) cannot
+    /// above 32767, since the mean would be divided by a saturated count.
+    pub fn score<O: AsRef<[T]>, Y: AsRef<[T]>>(loss: &impl Loss, pairs: impl IntoIterator<Item = (O, Y)>) -> Self {
+        let mut sum = T::ZERO;
+        let mut correct = 0;
+        let mut total = 0;
+
+        for (output, target) in pairs {
+            let (output, target) = (output.as_ref(), target.as_ref());
+            assert_eq!(output.len(), target.len(), "output and target have different widths");
+
+            sum = sum + loss.loss(output, target);
+            if is_correct(&loss.predict(output), target) {
+                correct += 1;
+            }
+            total += 1;
+        }
+
+        let loss = if total == 0 {
+            T::ZERO
+        } else {
+            sum / T::from_count(total).expect("too many examples for the numeric type")
+        };
+
+        Self { loss, correct, total }
+    }
+
+    /// The fraction of correctly predicted examples, zero for an empty evaluation set.
+    pub fn accuracy(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.correct as f32 / self.total as f32
+        }
+    }
+}
+
+fn is_correct<T: Scalar>(output: &[T], target: &[T]) -> bool {
+    match (output, target) {
+        ([o], [y]) => {
+            let half = T::from_f32(0.5);
+            (half < *o) == (half < *y)
+        }
+        _ => argmax(output) == argmax(target),
+    }
+}
+
+// The index of the first largest value.
+fn argmax<T: Scalar>(values: &[T]) -> Option<usize> {
+    let mut best: Option<(usize, T)> = None;
+    for (i, &v) in values.iter().enumerate() {
+        if best.map_or(true, |(_, b)| b < v) {
+            best = Some((i, v));
+        }
+    }
+
+    best.map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loss::Mse;
+    use crate::numeric::Q16;
+
+    // `total` examples with a single output, half of them with a squared error of one.
+    fn pairs<T: Scalar>(total: u32) -> impl Iterator<Item = ([T; 1], [T; 1])> {
+        (0..total).map(|i| ([T::from_f32((i % 2) as f32)], [T::ZERO]))
+    }
+
+    #[test]
+    fn averages_over_more_examples_than_q16_can_count() {
+        let metrics = Metrics::<f32>::score(&Mse, pairs(40000));
+        assert_eq!((metrics.correct, metrics.total), (20000, 40000));
+        assert_eq!(metrics.loss, 0.5);
+
+        let metrics = Metrics::<Q16>::score(&Mse, pairs(32766));
+        assert_eq!(metrics.loss.to_f32(), 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "too many examples for the numeric type")]
+    fn q16_rejects_more_examples_than_it_can_count() {
+        Metrics::<Q16>::score(&Mse, pairs(40000));
+    }
+}
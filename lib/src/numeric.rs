@@ -0,0 +1,388 @@
+//! Numeric backends for training.
+//!
+//! All training code is generic over [`Scalar`], so the same routine can run on `f32` or on
+//! saturating fixed-point numbers from the `fixed` crate. The crate-wide [`Fixed`] alias picks
+//! the backend used by the guest and the public values: `f32` by default, or [`Q16`] when the
+//! `i16f16` feature is enabled. [`Q32`] is only available through the type parameter because its
+//! raw representation does not fit the `uint32` public values.
+//!
+//! # Bit-exactness
+//!
+//! Every backend is evaluated with integer or IEEE-754 operations that are fully specified, so
+//! the host and the zkVM guest produce the same bits for the same inputs:
+//!
+//! * `f32` follows IEEE-754 binary32 with round-to-nearest-even. On the host this is done by the
+//!   FPU, inside the guest by the soft-float routines of `compiler_builtins`, which are correctly
+//!   rounded. Only the payload of NaN results is unspecified.
+//! * [`Q16`] and [`Q32`] are plain two's-complement integers and never touch floating point.
+//!
+//! # Overflow and rounding of the fixed-point backends
+//!
+//! * Addition, subtraction, multiplication, division and negation saturate at `MIN`/`MAX`
+//!   instead of wrapping. This does not depend on `debug_assertions`, so debug and release
+//!   builds agree.
+//! * Multiplication rounds toward negative infinity.
+//! * Division rounds toward zero, and division by zero panics.
+//! * [`Scalar::from_f32`] rounds to the nearest value, ties to even, and saturates out-of-range
+//!   values. It panics on NaN.
+//!
+//! ```
+//! use fibonacci_lib::numeric::{Q16, Scalar};
+//!
+//! // Saturating overflow.
+//! let big = Q16::from_f32(30000.0);
+//! assert_eq!((big + big).to_raw(), i32::MAX as u32);
+//!
+//! // Multiplication rounds toward negative infinity: -2^-16 * 0.5 stays at -2^-16.
+//! let tiny = Q16::from_raw(-1_i32 as u32);
+//! assert_eq!((tiny * Q16::from_f32(0.5)).to_raw(), -1_i32 as u32);
+//!
+//! // Division rounds toward zero.
+//! let third = -Q16::ONE / Q16::from_f32(3.0);
+//! assert_eq!(third.to_raw(), -21845_i32 as u32);
+//!
+//! // Conversion rounds to nearest.
+//! assert_eq!(Q16::from_f32(0.1).to_raw(), 6554);
+//! ```
+
+use core::fmt::Debug;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+use fixed::types::{I16F16, I32F32};
+use fixed::Saturating;
+use serde::{Deserialize, Serialize};
+
+/// Signed 16.16 saturating fixed-point number.
+pub type Q16 = Saturating<I16F16>;
+
+/// Signed 32.32 saturating fixed-point number.
+pub type Q32 = Saturating<I32F32>;
+
+/// The numeric type used by the guest program and encoded in the public values.
+#[cfg(not(feature = "i16f16"))]
+pub type Fixed = f32;
+
+/// The numeric type used by the guest program and encoded in the public values.
+#[cfg(feature = "i16f16")]
+pub type Fixed = Q16;
+
+/// The numeric type of raw values, recorded in checkpoints so that raw parameters are never read
+/// back as another type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumericType {
+    F32,
+    Q16,
+}
+
+impl NumericType {
+    /// The numeric type of [`Fixed`].
+    #[cfg(not(feature = "i16f16"))]
+    pub const FIXED: NumericType = NumericType::F32;
+
+    /// The numeric type of [`Fixed`].
+    #[cfg(feature = "i16f16")]
+    pub const FIXED: NumericType = NumericType::Q16;
+
+    pub fn to_u32(self) -> u32 {
+        match self {
+            NumericType::F32 => 0,
+            NumericType::Q16 => 1,
+        }
+    }
+
+    pub fn from_u32(i: u32) -> Option<Self> {
+        match i {
+            0 => Some(NumericType::F32),
+            1 => Some(NumericType::Q16),
+            _ => None,
+        }
+    }
+}
+
+/// A number that training code can be written against.
+pub trait Scalar:
+    Copy
+    + Debug
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// The raw bit pattern used to move values in and out of the zkVM.
+    type Raw: Copy + Debug + Eq;
+
+    fn from_raw(raw: Self::Raw) -> Self;
+
+    fn to_raw(self) -> Self::Raw;
+
+    fn from_f32(value: f32) -> Self;
+
+    fn to_f32(self) -> f32;
+
+    /// The count `n` as a divisor, or `None` if it is out of range, that is if `n + 1` is not
+    /// larger. The fixed-point backends saturate above their maximum, 32767 for [`Q16`], and `f32`
+    /// skips integers above 2^24.
+    fn from_count(n: u32) -> Option<Self> {
+        let value = Self::from_f32(n as f32);
+        (value < Self::from_f32(n as f32 + 1.0)).then_some(value)
+    }
+}
+
+impl Scalar for f32 {
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+
+    type Raw = u32;
+
+    fn from_raw(raw: u32) -> Self {
+        f32::from_bits(raw)
+    }
+
+    fn to_raw(self) -> u32 {
+        self.to_bits()
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+impl Scalar for Q16 {
+    const ZERO: Self = Saturating(I16F16::ZERO);
+    const ONE: Self = Saturating(I16F16::ONE);
+
+    type Raw = u32;
+
+    fn from_raw(raw: u32) -> Self {
+        Saturating(I16F16::from_bits(raw as i32))
+    }
+
+    fn to_raw(self) -> u32 {
+        self.0.to_bits() as u32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        Saturating(I16F16::saturating_from_num(value))
+    }
+
+    fn to_f32(self) -> f32 {
+        self.0.to_num()
+    }
+}
+
+impl Scalar for Q32 {
+    const ZERO: Self = Saturating(I32F32::ZERO);
+    const ONE: Self = Saturating(I32F32::ONE);
+
+    type Raw = u64;
+
+    fn from_raw(raw: u64) -> Self {
+        Saturating(I32F32::from_bits(raw as i64))
+    }
+
+    fn to_raw(self) -> u64 {
+        self.0.to_bits() as u64
+    }
+
+    fn from_f32(value: f32) -> Self {
+        Saturating(I32F32::saturating_from_num(value))
+    }
+
+    fn to_f32(self) -> f32 {
+        self.0.to_num()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::train_perceptron;
+    use alloc::vec::Vec;
+
+    fn q16(raw: i32) -> Q16 {
+        Q16::from_raw(raw as u32)
+    }
+
+    fn q32(raw: i64) -> Q32 {
+        Q32::from_raw(raw as u64)
+    }
+
+    #[test]
+    fn q16_saturates() {
+        let (min, max) = (q16(i32::MIN), q16(i32::MAX));
+        let two = Q16::from_f32(2.0);
+
+        assert_eq!(max + Q16::ONE, max);
+        assert_eq!(min - Q16::ONE, min);
+        assert_eq!(min + -Q16::ONE, min);
+        assert_eq!(max - -Q16::ONE, max);
+        assert_eq!(max * two, max);
+        assert_eq!(min * two, min);
+        assert_eq!(max * -two, min);
+        assert_eq!(min * -two, max);
+        assert_eq!(-min, max);
+        assert_eq!(max / Q16::from_raw(1), max);
+        assert_eq!(min / Q16::from_raw(1), min);
+    }
+
+    #[test]
+    fn q32_saturates() {
+        let (min, max) = (q32(i64::MIN), q32(i64::MAX));
+        let two = Q32::from_f32(2.0);
+
+        assert_eq!(max + Q32::ONE, max);
+        assert_eq!(min - Q32::ONE, min);
+        assert_eq!(max * two, max);
+        assert_eq!(min * two, min);
+        assert_eq!(max * -two, min);
+        assert_eq!(-min, max);
+    }
+
+    #[test]
+    fn mul_rounds_toward_negative_infinity() {
+        let half = Q16::from_f32(0.5);
+        assert_eq!(q16(3) * half, q16(1));
+        assert_eq!(q16(-3) * half, q16(-2));
+        assert_eq!(q16(1) * half, q16(0));
+        assert_eq!(q16(-1) * half, q16(-1));
+
+        let half = Q32::from_f32(0.5);
+        assert_eq!(q32(3) * half, q32(1));
+        assert_eq!(q32(-3) * half, q32(-2));
+    }
+
+    #[test]
+    fn div_rounds_toward_zero() {
+        let two = Q16::from_f32(2.0);
+        assert_eq!(q16(3) / two, q16(1));
+        assert_eq!(q16(-3) / two, q16(-1));
+        assert_eq!(Q16::ONE / Q16::from_f32(3.0), q16(21845));
+        assert_eq!(-Q16::ONE / Q16::from_f32(3.0), q16(-21845));
+
+        let two = Q32::from_f32(2.0);
+        assert_eq!(q32(3) / two, q32(1));
+        assert_eq!(q32(-3) / two, q32(-1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn q16_div_by_zero_panics() {
+        let _ = Q16::ONE / Q16::ZERO;
+    }
+
+    #[test]
+    #[should_panic]
+    fn q32_div_by_zero_panics() {
+        let _ = Q32::ONE / Q32::ZERO;
+    }
+
+    #[test]
+    fn from_f32_rounds_to_nearest_even() {
+        let lsb = 1.0 / 65536.0;
+        assert_eq!(Q16::from_f32(0.5 * lsb), q16(0));
+        assert_eq!(Q16::from_f32(1.5 * lsb), q16(2));
+        assert_eq!(Q16::from_f32(2.5 * lsb), q16(2));
+        assert_eq!(Q16::from_f32(-0.5 * lsb), q16(0));
+        assert_eq!(Q16::from_f32(-1.5 * lsb), q16(-2));
+        assert_eq!(Q16::from_f32(0.75 * lsb), q16(1));
+        assert_eq!(Q16::from_f32(0.1), q16(6554));
+    }
+
+    #[test]
+    fn from_f32_saturates() {
+        assert_eq!(Q16::from_f32(f32::INFINITY), q16(i32::MAX));
+        assert_eq!(Q16::from_f32(f32::NEG_INFINITY), q16(i32::MIN));
+        assert_eq!(Q16::from_f32(1e9), q16(i32::MAX));
+        assert_eq!(Q16::from_f32(-1e9), q16(i32::MIN));
+        assert_eq!(Q32::from_f32(f32::INFINITY), q32(i64::MAX));
+        assert_eq!(Q32::from_f32(f32::NEG_INFINITY), q32(i64::MIN));
+    }
+
+    #[test]
+    fn from_count_rejects_counts_out_of_range() {
+        assert_eq!(Q16::from_count(32767), Some(Q16::from_f32(32767.0)));
+        assert_eq!(Q16::from_count(32768), None);
+        assert_eq!(Q16::from_count(u32::MAX), None);
+        assert_eq!(Q32::from_count(40000), Some(Q32::from_f32(40000.0)));
+        assert_eq!(f32::from_count(40000), Some(40000.0));
+        assert_eq!(f32::from_count(1 << 24), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn q16_from_nan_panics() {
+        let _ = Q16::from_f32(f32::NAN);
+    }
+
+    #[test]
+    #[should_panic]
+    fn q32_from_nan_panics() {
+        let _ = Q32::from_f32(f32::NAN);
+    }
+
+    #[test]
+    fn raw_round_trips() {
+        for raw in [0, 1, 0x8000_0000, 0xffff_ffff, 0x3dcc_cccd] {
+            assert_eq!(Q16::from_raw(raw).to_raw(), raw);
+            assert_eq!(f32::from_raw(raw).to_raw(), raw);
+        }
+    }
+
+    // Train the perceptron on AND from weights that need several epochs to settle, and return the
+    // raw parameters after each epoch.
+    fn perceptron_trace<T: Scalar>() -> Vec<T::Raw> {
+        let [zero, one] = [T::ZERO, T::ONE];
+        let dataset = [[zero, zero, zero], [zero, one, zero], [one, zero, zero], [one, one, one]];
+        let (mut w_l, mut w_r, mut b) = (T::from_f32(0.3), T::from_f32(-0.2), T::from_f32(0.1));
+
+        let mut trace = Vec::new();
+        for _ in 0..6 {
+            (w_l, w_r, b) = train_perceptron(&dataset, 1, T::from_f32(0.15), w_l, w_r, b);
+            trace.extend([w_l, w_r, b].map(T::to_raw));
+        }
+        trace
+    }
+
+    // The guest runs the same code on the same integer and soft-float operations, so these bits
+    // are also the ones it commits.
+    #[test]
+    fn training_matches_golden_bits() {
+        #[rustfmt::skip]
+        assert_eq!(perceptron_trace::<f32>(), [
+            0x3e99999a, 0xbd4ccccc, 0xbd4cccd0,
+            0x3e99999a, 0x3dccccce, 0xbd4cccd0,
+            0x3e99999a, 0x3dccccce, 0xbe4cccce,
+            0x3e99999a, 0x3e800000, 0xbe4cccce,
+            0x3e99999a, 0x3dcccccc, 0xbeb33334,
+            0x3e99999a, 0x3dcccccc, 0xbeb33334,
+        ]);
+        #[rustfmt::skip]
+        assert_eq!(perceptron_trace::<Q16>(), [
+            0x4ccd, 0xfffff333, 0xfffff334,
+            0x4ccd, 0x1999, 0xfffff334,
+            0x4ccd, 0x1999, 0xffffccce,
+            0x4ccd, 0x3fff, 0xffffccce,
+            0x4ccd, 0x1999, 0xffffa668,
+            0x4ccd, 0x1999, 0xffffa668,
+        ]);
+        #[rustfmt::skip]
+        assert_eq!(perceptron_trace::<Q32>(), [
+            0x4ccccd00, 0xfffffffff3333340, 0xfffffffff3333320,
+            0x4ccccd00, 0x199999c0, 0xfffffffff3333320,
+            0x4ccccd00, 0x199999c0, 0xffffffffcccccca0,
+            0x4ccccd00, 0x40000040, 0xffffffffcccccca0,
+            0x4ccccd00, 0x199999c0, 0xffffffffa6666620,
+            0x4ccccd00, 0x199999c0, 0xffffffffa6666620,
+        ]);
+    }
+}
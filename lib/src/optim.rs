@@ -0,0 +1,432 @@
+//! Optimizers, learning rate schedules and mini-batch training.
+//!
+//! An epoch walks the dataset in order, in mini-batches of `batch_size` consecutive examples; the
+//! last batch of an epoch may be smaller. The gradients of a batch are averaged and handed to an
+//! [`Optimizer`], with the learning rate of the current epoch given by a [`Schedule`]. A batch
+//! size of one with [`Sgd`] and a constant schedule is plain per-example SGD. The average divides
+//! by the batch size in the numeric type, so a batch holds at most 32767 examples on [`Q16`].
+//!
+//! A [`TrainingState`] holds everything training needs besides the parameters: the loss, the
+//! optimizer and its moment estimates, the schedule, the batch size and the number of epochs
+//! trained so far. It encodes to `u32` words that are stored in checkpoints and covered by their
+//! digest, so a run resumed from a checkpoint continues exactly where the previous one stopped.
+//!
+//! [`Q16`]: crate::numeric::Q16
+//!
+//! The square root of Adam and the cosine of the cosine schedule are computed with the operations
+//! of [`Scalar`] only, by Newton's method and a Taylor polynomial, so every backend gives the same
+//! bits on the host and in the guest.
+//!
+//! ```
+//! use fibonacci_lib::optim::{cos_pi, sqrt};
+//!
+//! for x in [0.0f32, 0.1, 0.25, 0.5, 0.8, 1.0] {
+//!     assert!((cos_pi(x) - (core::f32::consts::PI * x).cos()).abs() < 1e-5);
+//! }
+//! for x in [0.0f32, 1e-6, 0.5, 2.0, 1e6] {
+//!     assert!((sqrt(x) - x.sqrt()).abs() <= 1e-6 * x.sqrt().max(1.0));
+//! }
+//! ```
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::commitment::tagged_digest;
+use crate::loss::{Loss, LossKind};
+use crate::Scalar;
+
+const OPTIMIZER_TAG: &[u8] = b"zkpot/optimizer/v2";
+
+// Newton's method halves a large input at each step before it converges quadratically, so this
+// covers inputs up to about 2^40.
+const SQRT_ITERATIONS: u32 = 32;
+
+/// Updates parameters from the averaged gradient of a mini-batch.
+pub trait Optimizer<T: Scalar> {
+    /// Move `params` against `grads`, the gradient of the loss laid out like `params`.
+    fn step(&mut self, params: &mut [T], grads: &[T], lr: T);
+}
+
+/// A model whose flat parameter vector can be trained by an [`Optimizer`].
+pub trait Trainable<T: Scalar> {
+    fn params(&self) -> Vec<T>;
+
+    fn set_params(&mut self, params: &[T]);
+
+    /// Add the gradient of `loss` on `example` to `grads`, laid out like [`Trainable::params`].
+    fn accumulate_gradient(&self, example: &[T], loss: &impl Loss, grads: &mut [T]);
+}
+
+/// `p -= lr * g`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sgd;
+
+/// SGD with momentum: `v = beta * v + g`, then `p -= lr * v`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Momentum<T> {
+    pub beta: T,
+    pub velocity: Vec<T>,
+}
+
+/// Adam with bias-corrected moment estimates. The powers of the betas are kept instead of a step
+/// count, so that no power has to be computed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adam<T> {
+    pub beta1: T,
+    pub beta2: T,
+    pub epsilon: T,
+    pub beta1_power: T,
+    pub beta2_power: T,
+    pub m: Vec<T>,
+    pub v: Vec<T>,
+}
+
+impl<T: Scalar> Momentum<T> {
+    pub fn new(beta: T, num_params: usize) -> Self {
+        Self { beta, velocity: vec![T::ZERO; num_params] }
+    }
+}
+
+impl<T: Scalar> Adam<T> {
+    pub fn new(beta1: T, beta2: T, epsilon: T, num_params: usize) -> Self {
+        Self {
+            beta1,
+            beta2,
+            epsilon,
+            beta1_power: T::ONE,
+            beta2_power: T::ONE,
+            m: vec![T::ZERO; num_params],
+            v: vec![T::ZERO; num_params],
+        }
+    }
+}
+
+impl<T: Scalar> Optimizer<T> for Sgd {
+    fn step(&mut self, params: &mut [T], grads: &[T], lr: T) {
+        for (p, &g) in params.iter_mut().zip(grads) {
+            *p = *p - lr * g;
+        }
+    }
+}
+
+impl<T: Scalar> Optimizer<T> for Momentum<T> {
+    fn step(&mut self, params: &mut [T], grads: &[T], lr: T) {
+        for ((p, v), &g) in params.iter_mut().zip(&mut self.velocity).zip(grads) {
+            *v = self.beta * *v + g;
+            *p = *p - lr * *v;
+        }
+    }
+}
+
+impl<T: Scalar> Optimizer<T> for Adam<T> {
+    fn step(&mut self, params: &mut [T], grads: &[T], lr: T) {
+        self.beta1_power = self.beta1_power * self.beta1;
+        self.beta2_power = self.beta2_power * self.beta2;
+        let (correction1, correction2) = (T::ONE - self.beta1_power, T::ONE - self.beta2_power);
+
+        for (((p, m), v), &g) in params.iter_mut().zip(&mut self.m).zip(&mut self.v).zip(grads) {
+            *m = self.beta1 * *m + (T::ONE - self.beta1) * g;
+            *v = self.beta2 * *v + (T::ONE - self.beta2) * g * g;
+
+            // A denominator that rounds to zero in fixed point skips the update instead of
+            // dividing by zero.
+            let denominator = sqrt(*v / correction2) + self.epsilon;
+            if T::ZERO < denominator {
+                *p = *p - lr * (*m / correction1) / denominator;
+            }
+        }
+    }
+}
+
+/// Which optimizer a [`TrainingState`] uses, encoded as a `u32`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptimizerKind {
+    Sgd,
+    Momentum,
+    Adam,
+}
+
+impl OptimizerKind {
+    pub fn to_u32(self) -> u32 {
+        match self {
+            OptimizerKind::Sgd => 0,
+            OptimizerKind::Momentum => 1,
+            OptimizerKind::Adam => 2,
+        }
+    }
+
+    pub fn from_u32(i: u32) -> Option<Self> {
+        match i {
+            0 => Some(OptimizerKind::Sgd),
+            1 => Some(OptimizerKind::Momentum),
+            2 => Some(OptimizerKind::Adam),
+            _ => None,
+        }
+    }
+}
+
+/// Any of the optimizers, so that the optimizer can be chosen at runtime.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnyOptimizer<T> {
+    Sgd(Sgd),
+    Momentum(Momentum<T>),
+    Adam(Adam<T>),
+}
+
+impl<T> AnyOptimizer<T> {
+    pub fn kind(&self) -> OptimizerKind {
+        match self {
+            AnyOptimizer::Sgd(_) => OptimizerKind::Sgd,
+            AnyOptimizer::Momentum(_) => OptimizerKind::Momentum,
+            AnyOptimizer::Adam(_) => OptimizerKind::Adam,
+        }
+    }
+}
+
+impl<T: Scalar> Optimizer<T> for AnyOptimizer<T> {
+    fn step(&mut self, params: &mut [T], grads: &[T], lr: T) {
+        match self {
+            AnyOptimizer::Sgd(sgd) => sgd.step(params, grads, lr),
+            AnyOptimizer::Momentum(momentum) => momentum.step(params, grads, lr),
+            AnyOptimizer::Adam(adam) => adam.step(params, grads, lr),
+        }
+    }
+}
+
+/// How the learning rate changes from epoch to epoch, as a factor of the base learning rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schedule<T> {
+    Constant,
+    /// Multiply the learning rate by `factor` every `every` epochs.
+    StepDecay { every: u32, factor: T },
+    /// Decay the learning rate along half a cosine, from the base learning rate to `min` times the
+    /// base learning rate after `epochs` epochs, and keep it there.
+    Cosine { epochs: u32, min: T },
+}
+
+impl<T: Scalar> Schedule<T> {
+    /// The factor of the base learning rate in epoch `epoch`, counted from zero.
+    pub fn factor(&self, epoch: u32) -> T {
+        match *self {
+            Schedule::Constant => T::ONE,
+            Schedule::StepDecay { every, factor } => {
+                let mut result = T::ONE;
+                for _ in 0..epoch / every {
+                    result = result * factor;
+                }
+                result
+            }
+            Schedule::Cosine { epochs, min } => {
+                let progress = T::from_f32(epoch.min(epochs) as f32) / T::from_f32(epochs as f32);
+                let half = T::from_f32(0.5);
+                min + (T::ONE - min) * half * (T::ONE + cos_pi(progress))
+            }
+        }
+    }
+
+    /// The factor in epoch `epoch`, given `factor`, the one of the epoch before. Step decay
+    /// multiplies once instead of raising `factor` again, with the same bits as [`Schedule::factor`].
+    pub fn next_factor(&self, epoch: u32, factor: T) -> T {
+        match *self {
+            Schedule::StepDecay { every, factor: decay } if epoch % every == 0 => factor * decay,
+            Schedule::StepDecay { .. } => factor,
+            _ => self.factor(epoch),
+        }
+    }
+}
+
+/// The loss, optimizer, schedule, batch size and epoch count of a training run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrainingState<T> {
+    pub loss: LossKind,
+    pub optimizer: AnyOptimizer<T>,
+    pub schedule: Schedule<T>,
+    pub batch_size: u32,
+    /// The number of epochs trained so far, which sets the learning rate of the schedule.
+    pub epoch: u32,
+}
+
+impl<T: Scalar> TrainingState<T> {
+    /// Per-example SGD on the squared error with a constant learning rate.
+    pub fn sgd() -> Self {
+        Self {
+            loss: LossKind::Mse,
+            optimizer: AnyOptimizer::Sgd(Sgd),
+            schedule: Schedule::Constant,
+            batch_size: 1,
+            epoch: 0,
+        }
+    }
+
+    /// Train `model` for `epochs` epochs over `dataset` with base learning rate `lr`.
+    ///
+    /// Panics if a batch holds more examples than [`Scalar::from_count`] can count in `T`.
+    pub fn train<M: Trainable<T>, R: AsRef<[T]>>(&mut self, model: &mut M, dataset: &[R], epochs: u32, lr: T) {
+        assert!(self.batch_size > 0, "batch size must be positive");
+        let mut params = model.params();
+        let mut grads = vec![T::ZERO; params.len()];
+
+        let mut factor = self.schedule.factor(self.epoch);
+        for epoch in 0..epochs {
+            if epoch > 0 {
+                factor = self.schedule.next_factor(self.epoch, factor);
+            }
+            let epoch_lr = lr * factor;
+            for batch in dataset.chunks(self.batch_size as usize) {
+                grads.fill(T::ZERO);
+                for example in batch {
+                    model.accumulate_gradient(example.as_ref(), &self.loss, &mut grads);
+                }
+                if batch.len() > 1 {
+                    let size = T::from_count(batch.len() as u32).expect("batch too large for the numeric type");
+                    grads.iter_mut().for_each(|g| *g = *g / size);
+                }
+
+                self.optimizer.step(&mut params, &grads, epoch_lr);
+                model.set_params(&params);
+            }
+            self.epoch += 1;
+        }
+    }
+}
+
+impl<T: Scalar<Raw = u32>> TrainingState<T> {
+    // The number of words before the optimizer state.
+    const HEADER_WORDS: usize = 10;
+
+    /// Encode as `[kind, batch size, schedule, schedule epochs, schedule factor, beta1, beta2,
+    /// epsilon, epoch, loss]`, followed by the velocity for momentum, or by the powers of the betas and
+    /// the moments `m` and `v` for Adam. Hyperparameters an optimizer does not use are zero.
+    pub fn encode(&self) -> Vec<u32> {
+        let (schedule, schedule_epochs, schedule_factor) = match self.schedule {
+            Schedule::Constant => (0, 0, 0),
+            Schedule::StepDecay { every, factor } => (1, every, factor.to_raw()),
+            Schedule::Cosine { epochs, min } => (2, epochs, min.to_raw()),
+        };
+        let (hyper, state) = match &self.optimizer {
+            AnyOptimizer::Sgd(_) => ([0; 3], Vec::new()),
+            AnyOptimizer::Momentum(m) => ([m.beta.to_raw(), 0, 0], m.velocity.clone()),
+            AnyOptimizer::Adam(a) => (
+                [a.beta1.to_raw(), a.beta2.to_raw(), a.epsilon.to_raw()],
+                [&[a.beta1_power, a.beta2_power][..], &a.m, &a.v].concat(),
+            ),
+        };
+
+        let kind = self.optimizer.kind().to_u32();
+        let mut words = vec![kind, self.batch_size, schedule, schedule_epochs, schedule_factor];
+        words.extend(hyper);
+        words.extend([self.epoch, self.loss.to_u32()]);
+        words.extend(state.into_iter().map(T::to_raw));
+        words
+    }
+
+    /// Inverse of [`TrainingState::encode`] for a model with `num_params` parameters. Returns
+    /// `None` on a malformed or non-canonical encoding.
+    pub fn decode(words: &[u32], num_params: usize) -> Option<Self> {
+        let (header, state) = words.split_at_checked(Self::HEADER_WORDS)?;
+        let &[kind, batch_size, schedule, schedule_epochs, schedule_factor, beta1, beta2, epsilon, epoch, loss] =
+            header
+        else {
+            unreachable!()
+        };
+        let state: Vec<T> = state.iter().copied().map(T::from_raw).collect();
+
+        let schedule = match (schedule, schedule_epochs, schedule_factor) {
+            (0, 0, 0) => Schedule::Constant,
+            (1, every, factor) if every > 0 => Schedule::StepDecay { every, factor: T::from_raw(factor) },
+            (2, epochs, min) if epochs > 0 => Schedule::Cosine { epochs, min: T::from_raw(min) },
+            _ => return None,
+        };
+        let optimizer = match (OptimizerKind::from_u32(kind)?, beta1, beta2, epsilon) {
+            (OptimizerKind::Sgd, 0, 0, 0) if state.is_empty() => AnyOptimizer::Sgd(Sgd),
+            (OptimizerKind::Momentum, beta, 0, 0) if state.len() == num_params => {
+                AnyOptimizer::Momentum(Momentum { beta: T::from_raw(beta), velocity: state })
+            }
+            (OptimizerKind::Adam, beta1, beta2, epsilon) if state.len() == 2 * num_params + 2 => {
+                let (m, v) = state[2..].split_at(num_params);
+                AnyOptimizer::Adam(Adam {
+                    beta1: T::from_raw(beta1),
+                    beta2: T::from_raw(beta2),
+                    epsilon: T::from_raw(epsilon),
+                    beta1_power: state[0],
+                    beta2_power: state[1],
+                    m: m.to_vec(),
+                    v: v.to_vec(),
+                })
+            }
+            _ => return None,
+        };
+        if batch_size == 0 {
+            return None;
+        }
+
+        Some(Self { loss: LossKind::from_u32(loss)?, optimizer, schedule, batch_size, epoch })
+    }
+
+    /// Digest of the training state, committed by runs that commit their weights in the clear.
+    pub fn digest(&self) -> [u8; 32] {
+        tagged_digest(OPTIMIZER_TAG, &[&self.encode()])
+    }
+}
+
+/// `cos(pi * x)` for `x` in `[0, 1]`, from a Taylor polynomial on `[0, 1/2]` and the symmetry
+/// `cos(pi * x) = -cos(pi * (1 - x))`. The error is below `1e-5`.
+pub fn cos_pi<T: Scalar>(x: T) -> T {
+    let half = T::from_f32(0.5);
+    if half < x {
+        return -cos_pi(T::ONE - x);
+    }
+
+    // Horner's scheme in `y^2` for `1 - y^2/2! + y^4/4! - ... - y^10/10!`, with `y = pi * x`.
+    let y = T::from_f32(core::f32::consts::PI) * x;
+    let y2 = y * y;
+    let mut result = T::ZERO;
+    for k in (1..=6).rev() {
+        let denominator = T::from_f32(((2 * k - 1) * (2 * k)) as f32);
+        result = T::ONE - y2 * result / denominator;
+    }
+    result
+}
+
+/// The square root of `x` by Newton's method, with a fixed number of iterations. Zero for
+/// non-positive inputs.
+pub fn sqrt<T: Scalar>(x: T) -> T {
+    if x <= T::ZERO {
+        return T::ZERO;
+    }
+
+    // Starting above the root, every iteration moves down towards it.
+    let half = T::from_f32(0.5);
+    let mut y = if x < T::ONE { T::ONE } else { x };
+    for _ in 0..SQRT_ITERATIONS {
+        y = half * (y + x / y);
+    }
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numeric::Q16;
+
+    fn next_factor_matches_factor<T: Scalar>() {
+        let schedules = [
+            Schedule::Constant,
+            Schedule::StepDecay { every: 3, factor: T::from_f32(0.9) },
+            Schedule::Cosine { epochs: 50, min: T::from_f32(0.1) },
+        ];
+        for schedule in schedules {
+            let mut factor = schedule.factor(0);
+            for epoch in 1..200 {
+                factor = schedule.next_factor(epoch, factor);
+                assert_eq!(factor, schedule.factor(epoch), "{:?} in epoch {}", schedule, epoch);
+            }
+        }
+    }
+
+    #[test]
+    fn next_factor_matches_factor_bit_for_bit() {
+        next_factor_matches_factor::<f32>();
+        next_factor_matches_factor::<Q16>();
+    }
+}
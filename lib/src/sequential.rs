@@ -0,0 +1,316 @@
+//! Dense feed-forward networks of arbitrary depth and width.
+//!
+//! A [`Sequential`] model is described by a list of [`LayerSpec`]s. Each layer computes
+//! `activation(W x + b)`. Training minimises any [`Loss`](crate::loss::Loss), the squared error
+//! `sum((y - yhat)^2)` by default, with any optimizer of the [`optim`](crate::optim) module, plain
+//! per-example SGD by default. The layer specs encode to `u32` triples so the architecture can be
+//! committed in the public values.
+
+use alloc::vec::Vec;
+
+use crate::activation::{Activation, ActivationKind};
+use crate::loss::Loss;
+use crate::optim::{Trainable, TrainingState};
+use crate::{Scalar, Tensor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayerSpec {
+    pub input: u32,
+    pub output: u32,
+    pub activation: ActivationKind,
+}
+
+impl LayerSpec {
+    pub fn new(input: u32, output: u32, activation: ActivationKind) -> Self {
+        Self { input, output, activation }
+    }
+
+    /// Number of weights plus biases in the layer, or `None` if it does not fit a `u32`.
+    ///
+    /// The count is computed in `u32` so that the host and the 32-bit guest accept the same
+    /// architectures, and a checkpoint can only hold that many parameters anyway.
+    pub fn num_params(&self) -> Option<usize> {
+        let count = self.input.checked_add(1)?.checked_mul(self.output)?;
+        Some(count as usize)
+    }
+
+    /// Number of parameters of a model made of `specs`, or `None` if it does not fit a `u32`.
+    pub fn total_params(specs: &[LayerSpec]) -> Option<usize> {
+        specs.iter().try_fold(0u32, |total, spec| total.checked_add(spec.num_params()? as u32)).map(|n| n as usize)
+    }
+
+    /// Flatten specs into `(input, output, activation)` triples.
+    pub fn encode_all(specs: &[LayerSpec]) -> Vec<u32> {
+        specs.iter().flat_map(|s| [s.input, s.output, s.activation.to_u32()]).collect()
+    }
+
+    /// Whether `specs` has at least one layer and every layer takes the outputs of the one before.
+    pub fn connected(specs: &[LayerSpec]) -> bool {
+        !specs.is_empty() && specs.windows(2).all(|pair| pair[0].output == pair[1].input)
+    }
+
+    /// Inverse of [`LayerSpec::encode_all`]. Returns `None` on a malformed encoding, if the
+    /// layers do not [connect](LayerSpec::connected), or if the architecture has too many parameters
+    /// to count in a `u32`, so that any architecture it returns builds a [`Sequential`] model.
+    pub fn decode_all(words: &[u32]) -> Option<Vec<LayerSpec>> {
+        if words.len() % 3 != 0 {
+            return None;
+        }
+
+        let specs: Vec<LayerSpec> = words
+            .chunks_exact(3)
+            .map(|w| Some(LayerSpec::new(w[0], w[1], ActivationKind::from_u32(w[2])?)))
+            .collect::<Option<_>>()?;
+        if !LayerSpec::connected(&specs) {
+            return None;
+        }
+        LayerSpec::total_params(&specs)?;
+
+        Some(specs)
+    }
+}
+
+/// A fully connected layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dense<T> {
+    pub weights: Tensor<T>,
+    pub bias: Tensor<T>,
+    pub activation: ActivationKind,
+}
+
+/// Gradients of the loss with respect to one layer's parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DenseGrad<T> {
+    pub weights: Tensor<T>,
+    pub bias: Tensor<T>,
+}
+
+/// Intermediate values of a forward pass that the backward pass needs.
+#[derive(Clone, Debug)]
+pub struct ForwardCache<T> {
+    // The input of every layer, followed by the network output.
+    activations: Vec<Tensor<T>>,
+    // The pre-activation `W x + b` of every layer.
+    pre_activations: Vec<Tensor<T>>,
+}
+
+impl<T> ForwardCache<T> {
+    pub fn output(&self) -> &Tensor<T> {
+        self.activations.last().expect("cache always holds the network input")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sequential<T> {
+    layers: Vec<Dense<T>>,
+}
+
+impl<T: Scalar> Sequential<T> {
+    /// Build a model from specs and a flat parameter vector.
+    ///
+    /// Parameters are laid out layer by layer, each layer as its row-major weights followed by
+    /// its biases. Panics if consecutive layers do not connect or the parameter count is wrong.
+    pub fn from_params(specs: &[LayerSpec], params: &[T]) -> Self {
+        assert!(!specs.is_empty(), "a model needs at least one layer");
+        for pair in specs.windows(2) {
+            assert_eq!(pair[0].output, pair[1].input, "layer widths do not connect");
+        }
+        let expected = LayerSpec::total_params(specs).expect("too many parameters");
+        assert_eq!(params.len(), expected, "expected {} parameters", expected);
+
+        let mut offset = 0;
+        let layers = specs
+            .iter()
+            .map(|spec| {
+                let (rows, cols) = (spec.output as usize, spec.input as usize);
+                let weights = Tensor::new(rows, cols, params[offset..offset + rows * cols].to_vec());
+                offset += rows * cols;
+                let bias = Tensor::column(params[offset..offset + rows].to_vec());
+                offset += rows;
+
+                Dense { weights, bias, activation: spec.activation }
+            })
+            .collect();
+
+        Self { layers }
+    }
+
+    /// Build a model with small pseudo-random parameters in `[-0.5, 0.5)`.
+    ///
+    /// The generator is a fixed xorshift, so a seed always yields the same model.
+    pub fn seeded(specs: &[LayerSpec], seed: u64) -> Self {
+        let mut state = seed | 1;
+        let count = LayerSpec::total_params(specs).expect("too many parameters");
+        let params: Vec<T> = (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                T::from_f32((state >> 40) as f32 / (1u64 << 24) as f32 - 0.5)
+            })
+            .collect();
+
+        Self::from_params(specs, &params)
+    }
+
+    pub fn specs(&self) -> Vec<LayerSpec> {
+        self.layers
+            .iter()
+            .map(|layer| {
+                let (output, input) = layer.weights.shape();
+                LayerSpec::new(input as u32, output as u32, layer.activation)
+            })
+            .collect()
+    }
+
+    pub fn layers(&self) -> &[Dense<T>] {
+        &self.layers
+    }
+
+    /// The flat parameter vector, in the layout accepted by [`Sequential::from_params`].
+    pub fn params(&self) -> Vec<T> {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.weights.data().iter().chain(layer.bias.data()).copied())
+            .collect()
+    }
+
+    pub fn input_dim(&self) -> usize {
+        self.layers[0].weights.shape().1
+    }
+
+    pub fn output_dim(&self) -> usize {
+        self.layers[self.layers.len() - 1].weights.shape().0
+    }
+
+    pub fn forward(&self, x: &Tensor<T>) -> ForwardCache<T> {
+        let mut activations = Vec::with_capacity(self.layers.len() + 1);
+        let mut pre_activations = Vec::with_capacity(self.layers.len());
+        activations.push(x.clone());
+
+        for layer in &self.layers {
+            let z = layer.weights.matmul(&activations[activations.len() - 1]).add(&layer.bias);
+            activations.push(z.map(|v| layer.activation.forward(v)));
+            pre_activations.push(z);
+        }
+
+        ForwardCache { activations, pre_activations }
+    }
+
+    /// Backpropagate `loss` of the cached forward pass against the target `y`.
+    pub fn backward(&self, cache: &ForwardCache<T>, y: &Tensor<T>, loss: &impl Loss) -> Vec<DenseGrad<T>> {
+        let mut grad = Tensor::column(loss.gradient(cache.output().data(), y.data()));
+        let mut grads = Vec::with_capacity(self.layers.len());
+
+        for (i, layer) in self.layers.iter().enumerate().rev() {
+            let delta = grad.mul(&cache.pre_activations[i].map(|z| layer.activation.derivative(z)));
+            grads.push(DenseGrad {
+                weights: delta.matmul(&cache.activations[i].transpose()),
+                bias: delta.clone(),
+            });
+            grad = layer.weights.transpose().matmul(&delta);
+        }
+
+        grads.reverse();
+        grads
+    }
+
+    /// Take one gradient descent step.
+    pub fn sgd_update(&mut self, grads: &[DenseGrad<T>], lr: T) {
+        for (layer, grad) in self.layers.iter_mut().zip(grads) {
+            layer.weights = layer.weights.sub(&grad.weights.scale(lr));
+            layer.bias = layer.bias.sub(&grad.bias.scale(lr));
+        }
+    }
+
+    /// Train for `epochs` passes over `dataset`, one SGD step on the squared error per example.
+    ///
+    /// Each row holds the inputs followed by the targets.
+    pub fn train<R: AsRef<[T]>>(&mut self, dataset: &[R], epochs: u32, lr: T) {
+        self.train_with(dataset, epochs, lr, &mut TrainingState::sgd());
+    }
+
+    /// Like [`Sequential::train`], but with the loss, optimizer, schedule and batch size of
+    /// `training`, which is updated to continue from where this call stops.
+    pub fn train_with<R: AsRef<[T]>>(&mut self, dataset: &[R], epochs: u32, lr: T, training: &mut TrainingState<T>) {
+        training.train(self, dataset, epochs, lr);
+    }
+}
+
+impl<T: Scalar> Trainable<T> for Sequential<T> {
+    fn params(&self) -> Vec<T> {
+        Sequential::params(self)
+    }
+
+    fn set_params(&mut self, params: &[T]) {
+        let mut values = params.iter().copied();
+        for layer in &mut self.layers {
+            let (rows, cols) = layer.weights.shape();
+            layer.weights = Tensor::new(rows, cols, values.by_ref().take(rows * cols).collect());
+            layer.bias = Tensor::column(values.by_ref().take(rows).collect());
+        }
+    }
+
+    fn accumulate_gradient(&self, example: &[T], loss: &impl Loss, grads: &mut [T]) {
+        let (input, output) = (self.input_dim(), self.output_dim());
+        let x = Tensor::column(example[..input].to_vec());
+        let y = Tensor::column(example[input..input + output].to_vec());
+
+        let cache = self.forward(&x);
+        let layer_grads = self.backward(&cache, &y, loss);
+        let flat = layer_grads.iter().flat_map(|grad| grad.weights.data().iter().chain(grad.bias.data()));
+        for (g, &d) in grads.iter_mut().zip(flat) {
+            *g = *g + d;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn counts_params() {
+        let specs = [LayerSpec::new(2, 4, ActivationKind::Relu), LayerSpec::new(4, 1, ActivationKind::Identity)];
+        assert_eq!(specs[0].num_params(), Some(12));
+        assert_eq!(LayerSpec::total_params(&specs), Some(17));
+        assert_eq!(LayerSpec::decode_all(&LayerSpec::encode_all(&specs)), Some(specs.to_vec()));
+    }
+
+    #[test]
+    fn rejects_oversized_architectures() {
+        let relu = ActivationKind::Relu.to_u32();
+        assert_eq!(LayerSpec::new(u32::MAX, 1, ActivationKind::Relu).num_params(), None);
+        assert_eq!(LayerSpec::new(65536, 65536, ActivationKind::Relu).num_params(), None);
+
+        // Each layer fits, but not their sum.
+        let layer = LayerSpec::new(65535, 65535, ActivationKind::Relu);
+        assert!(layer.num_params().is_some());
+        assert_eq!(LayerSpec::total_params(&[layer, layer]), None);
+
+        assert_eq!(LayerSpec::decode_all(&[65536, 65536, relu]), None);
+        assert_eq!(LayerSpec::decode_all(&[65535, 65535, relu, 65535, 65535, relu]), None);
+        assert_eq!(LayerSpec::decode_all(&[u32::MAX, 1, relu]), None);
+    }
+
+    #[test]
+    fn rejects_disconnected_architectures() {
+        let (relu, identity) = (ActivationKind::Relu.to_u32(), ActivationKind::Identity.to_u32());
+        assert_eq!(LayerSpec::decode_all(&[]), None);
+        assert_eq!(LayerSpec::decode_all(&[2, 4, relu, 3, 1, identity]), None);
+        assert!(!LayerSpec::connected(&[]));
+        assert!(LayerSpec::connected(&[LayerSpec::new(2, 1, ActivationKind::Identity)]));
+    }
+
+    #[test]
+    fn params_round_trip() {
+        let specs = [LayerSpec::new(3, 2, ActivationKind::Sigmoid), LayerSpec::new(2, 2, ActivationKind::Identity)];
+        let model = Sequential::<f32>::seeded(&specs, 7);
+        let params = model.params();
+        assert_eq!(params.len(), 14);
+        assert_eq!(Sequential::from_params(&specs, &params), model);
+        assert_eq!(model.specs(), specs);
+        assert_eq!(model.forward(&Tensor::column(vec![1.0, 2.0, 3.0])).output().shape(), (2, 1));
+    }
+}
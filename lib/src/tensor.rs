@@ -0,0 +1,190 @@
+//! A small row-major matrix type for writing models without unrolling every weight by hand.
+//!
+//! Only `core` and `alloc` are used, so the module compiles for the zkVM guest. Vectors are
+//! represented as single-column tensors.
+//!
+//! The arithmetic is evaluated in a fixed order so results are reproducible bit for bit: a
+//! matrix product sums the terms of each dot product from left to right, starting with the first
+//! term rather than with zero. An empty dot product is zero.
+
+use alloc::vec::Vec;
+
+use crate::Scalar;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tensor<T> {
+    rows: usize,
+    cols: usize,
+    data: Vec<T>,
+}
+
+impl<T: Copy> Tensor<T> {
+    /// Create a tensor from row-major data.
+    pub fn new(rows: usize, cols: usize, data: Vec<T>) -> Self {
+        assert_eq!(data.len(), rows * cols, "tensor data does not match shape {}x{}", rows, cols);
+        Self { rows, cols, data }
+    }
+
+    /// Create a single-column tensor.
+    pub fn column(data: Vec<T>) -> Self {
+        Self::new(data.len(), 1, data)
+    }
+
+    pub fn from_fn(rows: usize, cols: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let mut data = Vec::with_capacity(rows * cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                data.push(f(i, j));
+            }
+        }
+
+        Self { rows, cols, data }
+    }
+
+    pub fn fill(rows: usize, cols: usize, value: T) -> Self {
+        Self::from_fn(rows, cols, |_, _| value)
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> T {
+        assert!(row < self.rows && col < self.cols, "index ({}, {}) out of bounds", row, col);
+        self.data[row * self.cols + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: T) {
+        assert!(row < self.rows && col < self.cols, "index ({}, {}) out of bounds", row, col);
+        self.data[row * self.cols + col] = value;
+    }
+
+    /// The elements in row-major order.
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<T> {
+        self.data
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.cols, self.rows, |i, j| self.get(j, i))
+    }
+
+    /// Apply `f` to every element.
+    pub fn map<U: Copy>(&self, f: impl FnMut(T) -> U) -> Tensor<U> {
+        Tensor { rows: self.rows, cols: self.cols, data: self.data.iter().copied().map(f).collect() }
+    }
+
+    /// Combine two tensors elementwise, broadcasting dimensions of size one.
+    ///
+    /// A dimension broadcasts if it is `1` on either side, so a column vector can be added to
+    /// every column of a matrix and a row vector to every row.
+    pub fn broadcast<U: Copy, V: Copy>(
+        &self,
+        other: &Tensor<U>,
+        mut f: impl FnMut(T, U) -> V,
+    ) -> Tensor<V> {
+        let rows = broadcast_dim(self.rows, other.rows);
+        let cols = broadcast_dim(self.cols, other.cols);
+
+        Tensor::from_fn(rows, cols, |i, j| {
+            let lhs = self.get(i.min(self.rows - 1), j.min(self.cols - 1));
+            let rhs = other.get(i.min(other.rows - 1), j.min(other.cols - 1));
+            f(lhs, rhs)
+        })
+    }
+}
+
+impl<T: Scalar> Tensor<T> {
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Self::fill(rows, cols, T::ZERO)
+    }
+
+    /// Matrix product `self × other`.
+    pub fn matmul(&self, other: &Self) -> Self {
+        assert_eq!(
+            self.cols, other.rows,
+            "cannot multiply {}x{} by {}x{}",
+            self.rows, self.cols, other.rows, other.cols
+        );
+
+        Self::from_fn(self.rows, other.cols, |i, j| {
+            if self.cols == 0 {
+                return T::ZERO;
+            }
+
+            let mut acc = self.get(i, 0) * other.get(0, j);
+            for k in 1..self.cols {
+                acc = acc + self.get(i, k) * other.get(k, j);
+            }
+            acc
+        })
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        self.broadcast(other, |a, b| a + b)
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.broadcast(other, |a, b| a - b)
+    }
+
+    /// Elementwise (Hadamard) product.
+    pub fn mul(&self, other: &Self) -> Self {
+        self.broadcast(other, |a, b| a * b)
+    }
+
+    pub fn scale(&self, factor: T) -> Self {
+        self.map(|a| factor * a)
+    }
+}
+
+fn broadcast_dim(lhs: usize, rhs: usize) -> usize {
+    match (lhs, rhs) {
+        (a, b) if a == b => a,
+        (1, b) => b,
+        (a, 1) => a,
+        (a, b) => panic!("cannot broadcast dimensions {} and {}", a, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn matmul_sums_in_order() {
+        let a = Tensor::new(2, 3, vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let b = Tensor::new(3, 2, vec![7.0f32, 8.0, 9.0, 10.0, 11.0, 12.0]);
+        assert_eq!(a.matmul(&b), Tensor::new(2, 2, vec![58.0, 64.0, 139.0, 154.0]));
+
+        // 1e8 + 1 - 1e8 is 0 in f32 when summed from the left, not 1.
+        let row = Tensor::new(1, 3, vec![1e8f32, 1.0, -1e8]);
+        assert_eq!(row.matmul(&Tensor::fill(3, 1, 1.0)).data(), [0.0]);
+    }
+
+    #[test]
+    fn matmul_of_zero_width_is_zero() {
+        let a = Tensor::<f32>::zeros(2, 0);
+        let b = Tensor::<f32>::zeros(0, 3);
+        assert_eq!(a.matmul(&b), Tensor::zeros(2, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot multiply 2x3 by 2x3")]
+    fn matmul_rejects_mismatched_shapes() {
+        let a = Tensor::<f32>::zeros(2, 3);
+        a.matmul(&a);
+    }
+
+    #[test]
+    fn broadcasts_columns_and_rows() {
+        let m = Tensor::new(2, 2, vec![1.0f32, 2.0, 3.0, 4.0]);
+        assert_eq!(m.add(&Tensor::column(vec![10.0, 20.0])).data(), [11.0, 12.0, 23.0, 24.0]);
+        assert_eq!(m.add(&Tensor::new(1, 2, vec![10.0, 20.0])).data(), [11.0, 22.0, 13.0, 24.0]);
+        assert_eq!(m.transpose().data(), [1.0, 3.0, 2.0, 4.0]);
+    }
+}
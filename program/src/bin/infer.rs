@@ -0,0 +1,21 @@
+//! A program that proves a prediction of the model of a checkpoint on one input.
+
+// These two lines are necessary for the program to properly compile.
+//
+// Under the hood, we wrap your main function with some extra code so that it behaves properly
+// inside the zkVM.
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use fibonacci_lib::GuestMode;
+use fibonacci_program::{prove_inference, read_header};
+
+pub fn main() {
+    // Inference always commits digests, the commitment word only keeps the header uniform.
+    read_header(&[GuestMode::Infer]);
+    let bytes = prove_inference();
+
+    // Commit to the public values of the program. The final proof will have a commitment to all the
+    // bytes that were committed to.
+    sp1_zkvm::io::commit_slice(&bytes);
+}
@@ -0,0 +1,25 @@
+//! A program that proves training of a dense network: a `Sequential` network of any depth, or the
+//! two-layer `MLP`.
+
+// These two lines are necessary for the program to properly compile.
+//
+// Under the hood, we wrap your main function with some extra code so that it behaves properly
+// inside the zkVM.
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use fibonacci_lib::GuestMode;
+use fibonacci_program::{prove_mlp, prove_sequential, read_header};
+
+pub fn main() {
+    // Read which network to train and how to commit to its weights.
+    let (mode, commitment) = read_header(&[GuestMode::Sequential, GuestMode::Mlp]);
+    let bytes = match mode {
+        GuestMode::Sequential => prove_sequential(commitment),
+        _ => prove_mlp(commitment),
+    };
+
+    // Commit to the public values of the program. The final proof will have a commitment to all the
+    // bytes that were committed to.
+    sp1_zkvm::io::commit_slice(&bytes);
+}
@@ -0,0 +1,21 @@
+//! A program that proves training of a two-input perceptron.
+
+// These two lines are necessary for the program to properly compile.
+//
+// Under the hood, we wrap your main function with some extra code so that it behaves properly
+// inside the zkVM.
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use fibonacci_lib::GuestMode;
+use fibonacci_program::{prove_perceptron, read_header};
+
+pub fn main() {
+    // Read how to commit to the weights.
+    let (_, commitment) = read_header(&[GuestMode::Perceptron]);
+    let bytes = prove_perceptron(commitment);
+
+    // Commit to the public values of the program. The final proof will have a commitment to all the
+    // bytes that were committed to.
+    sp1_zkvm::io::commit_slice(&bytes);
+}
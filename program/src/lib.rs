@@ -0,0 +1,242 @@
+//! The routines of the guest programs that prove training of a model, and inference with it. Each
+//! guest is a binary of this crate, with its own ELF and verification key:
+//!
+//! - `perceptron` trains a two-input perceptron,
+//! - `mlp` trains a `Sequential` dense network or the two-layer `MLP`,
+//! - `infer` runs the model of a checkpoint on one input.
+//!
+//! Every guest reads the same header: a word naming the guest mode, which must be one the guest
+//! runs, and a word selecting what is committed: either the inputs, the architecture and the
+//! trained weights in the clear, or only the digests of the checkpoints before and after training.
+//! The model itself is read as a `Checkpoint`, whose header must name the same model, and is
+//! trained with the optimizer state the checkpoint holds. In the clear, the optimizer and digests of
+//! its state are committed too.
+//!
+//! Training runs on a built-in dataset, or on examples read from stdin when the dataset id is
+//! `EXTERNAL_DATASET`. Either way the Merkle root of the examples is committed.
+//!
+//! The checkpoint also names the loss to train with: the squared error, or one of the
+//! cross-entropies for classifiers. The trained model is then evaluated with that loss on the
+//! training set, or on a held-out set read the same way, and the loss, its value and the accuracy
+//! are committed next to the Merkle root of that set.
+//!
+//! Inference commits the prediction next to the digests of the checkpoint and of the input.
+
+use alloy_sol_types::SolType;
+use fibonacci_lib::{
+    evaluate_mlp_on, evaluate_perceptron_on, evaluate_sequential_on, input_digest, predict,
+    update_checkpoint, Checkpoint, Commitment, DatasetId, DigestPublicValuesStruct, EvaluationStruct,
+    GuestMode, InferencePublicValuesStruct, LossKind, MerkleTree, MlpPublicValuesStruct, OptimizerStruct,
+    PublicValuesStruct, SequentialPublicValuesStruct, EXTERNAL_DATASET,
+};
+
+/// Read the header of the inputs: the guest mode, which must be one of `modes`, and how to commit
+/// to the weights.
+pub fn read_header(modes: &[GuestMode]) -> (GuestMode, Commitment) {
+    let mode = sp1_zkvm::io::read::<u32>();
+    let mode = GuestMode::from_u32(mode).expect("unknown guest mode");
+    assert!(modes.contains(&mode), "this program does not run {:?}", mode);
+
+    let commitment = sp1_zkvm::io::read::<u32>();
+    let commitment = Commitment::from_u32(commitment).expect("unknown commitment");
+
+    (mode, commitment)
+}
+
+/// Train a perceptron and encode the public values of the run.
+pub fn prove_perceptron(commitment: Commitment) -> Vec<u8> {
+    // Read an inputs into the training step.
+    let checkpoint = load_checkpoint();
+    let (w_l, w_r, b) = checkpoint.to_perceptron().unwrap_or_else(|e| panic!("{}", e));
+    let d = sp1_zkvm::io::read::<u32>();
+    let (rows, root) = load_dataset(d);
+    let t = sp1_zkvm::io::read::<u32>();
+    let r = sp1_zkvm::io::read::<u32>();
+    let held_out = load_held_out();
+
+    // Compute a weight update.
+    let updated = update_checkpoint(&checkpoint, &rows, t, r);
+    let [updated_w_l, updated_w_r, updated_b] = updated.params()[..] else { unreachable!() };
+
+    // Evaluate the updated weights.
+    let (eval_rows, eval_root) = evaluation_set(&held_out, &rows, root);
+    let metrics = evaluate_perceptron_on(eval_rows, updated_w_l, updated_w_r, updated_b);
+    let evaluation = EvaluationStruct::new(eval_root, LossKind::Mse, metrics);
+
+    // Encode the public values of the program.
+    match commitment {
+        Commitment::Clear => PublicValuesStruct::abi_encode(&PublicValuesStruct {
+            d,
+            dataset_root: root.into(),
+            t,
+            r,
+            w_l,
+            w_r,
+            b,
+            updated_w_l,
+            updated_w_r,
+            updated_b,
+            optimizer: OptimizerStruct::new(&checkpoint.training(), &updated.training()),
+            evaluation,
+        }),
+        Commitment::Digest => DigestPublicValuesStruct::abi_encode(&DigestPublicValuesStruct::new(
+            root,
+            t,
+            r,
+            &checkpoint,
+            &updated,
+            evaluation,
+        )),
+    }
+}
+
+/// Train a `Sequential` dense network and encode the public values of the run.
+pub fn prove_sequential(commitment: Commitment) -> Vec<u8> {
+    // Read the model and the training inputs.
+    let checkpoint = load_checkpoint();
+    checkpoint.check(GuestMode::Sequential).unwrap_or_else(|e| panic!("{}", e));
+    let (layers, params) = (checkpoint.architecture().to_vec(), checkpoint.params().to_vec());
+    let d = sp1_zkvm::io::read::<u32>();
+    let (rows, root) = load_dataset(d);
+    let t = sp1_zkvm::io::read::<u32>();
+    let r = sp1_zkvm::io::read::<u32>();
+    let held_out = load_held_out();
+
+    // Train the model.
+    let updated = update_checkpoint(&checkpoint, &rows, t, r);
+    let updated_params = updated.params().to_vec();
+
+    // Evaluate the trained model.
+    let (eval_rows, eval_root) = evaluation_set(&held_out, &rows, root);
+    let loss = checkpoint.training().loss;
+    let evaluation =
+        EvaluationStruct::new(eval_root, loss, evaluate_sequential_on(&layers, eval_rows, &updated_params, loss));
+
+    // Encode the public values of the program, including the architecture that was trained.
+    match commitment {
+        Commitment::Clear => SequentialPublicValuesStruct::abi_encode(&SequentialPublicValuesStruct {
+            layers,
+            d,
+            dataset_root: root.into(),
+            t,
+            r,
+            params,
+            updated_params,
+            optimizer: OptimizerStruct::new(&checkpoint.training(), &updated.training()),
+            evaluation,
+        }),
+        Commitment::Digest => DigestPublicValuesStruct::abi_encode(&DigestPublicValuesStruct::new(
+            root,
+            t,
+            r,
+            &checkpoint,
+            &updated,
+            evaluation,
+        )),
+    }
+}
+
+/// Train the two-layer `MLP` and encode the public values of the run.
+pub fn prove_mlp(commitment: Commitment) -> Vec<u8> {
+    // Read the model and the training inputs.
+    let checkpoint = load_checkpoint();
+    let mlp = checkpoint.to_mlp().unwrap_or_else(|e| panic!("{}", e));
+    let (shape, params) = (mlp.shape(), mlp.params());
+    let d = sp1_zkvm::io::read::<u32>();
+    let (rows, root) = load_dataset(d);
+    let t = sp1_zkvm::io::read::<u32>();
+    let r = sp1_zkvm::io::read::<u32>();
+    let held_out = load_held_out();
+
+    // Train the model.
+    let updated = update_checkpoint(&checkpoint, &rows, t, r);
+    let updated_mlp = updated.to_mlp().unwrap_or_else(|e| panic!("{}", e));
+
+    // Evaluate the trained model.
+    let (eval_rows, eval_root) = evaluation_set(&held_out, &rows, root);
+    let loss = checkpoint.training().loss;
+    let evaluation = EvaluationStruct::new(eval_root, loss, evaluate_mlp_on(&updated_mlp, eval_rows, loss));
+    let updated_params = updated_mlp.params();
+
+    // Encode the public values of the program.
+    match commitment {
+        Commitment::Clear => MlpPublicValuesStruct::abi_encode(&MlpPublicValuesStruct {
+            shape: shape.to_vec(),
+            d,
+            dataset_root: root.into(),
+            t,
+            r,
+            params,
+            updated_params,
+            optimizer: OptimizerStruct::new(&checkpoint.training(), &updated.training()),
+            evaluation,
+        }),
+        Commitment::Digest => DigestPublicValuesStruct::abi_encode(&DigestPublicValuesStruct::new(
+            root,
+            t,
+            r,
+            &checkpoint,
+            &updated,
+            evaluation,
+        )),
+    }
+}
+
+/// Run the model of a checkpoint on one input and encode the prediction.
+pub fn prove_inference() -> Vec<u8> {
+    // Read the model and the input to run it on.
+    // Any trained model can make a prediction, so only the numeric type is checked.
+    let checkpoint = load_checkpoint();
+    checkpoint.check(checkpoint.model()).unwrap_or_else(|e| panic!("{}", e));
+    let input = sp1_zkvm::io::read::<Vec<u32>>();
+
+    // Run the same forward pass as training.
+    let output = predict(checkpoint.model(), checkpoint.architecture(), checkpoint.params(), &input);
+
+    // Encode the public values of the program.
+    InferencePublicValuesStruct::abi_encode(&InferencePublicValuesStruct {
+        model: checkpoint.model().to_u32(),
+        weights_digest: checkpoint.digest().into(),
+        input_digest: input_digest(&input).into(),
+        output,
+    })
+}
+
+/// Read a checkpoint as a single byte vector. A checkpoint whose digest does not match its contents
+/// aborts the proof.
+fn load_checkpoint() -> Checkpoint {
+    Checkpoint::from_bytes(&sp1_zkvm::io::read_vec()).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Read the examples of an external dataset if `d` asks for one, otherwise use the built-in
+/// dataset `d`. An unknown id aborts the proof rather than training on another dataset. Returns
+/// the examples and their Merkle root.
+fn load_dataset(d: u32) -> (Vec<Vec<u32>>, [u8; 32]) {
+    let rows = if d == EXTERNAL_DATASET {
+        sp1_zkvm::io::read::<Vec<Vec<u32>>>()
+    } else {
+        DatasetId::try_from(d).unwrap_or_else(|e| panic!("{}", e)).rows()
+    };
+    let root = MerkleTree::new(&rows).root();
+
+    (rows, root)
+}
+
+/// Read the held-out set to evaluate the trained model on: nothing to evaluate on the training set,
+/// or a dataset id followed, for `EXTERNAL_DATASET`, by its examples.
+fn load_held_out() -> Option<(Vec<Vec<u32>>, [u8; 32])> {
+    sp1_zkvm::io::read::<Option<u32>>().map(load_dataset)
+}
+
+/// The examples and Merkle root of the evaluation set: the held-out set if there is one,
+/// otherwise the training set.
+fn evaluation_set<'a>(
+    held_out: &'a Option<(Vec<Vec<u32>>, [u8; 32])>,
+    rows: &'a [Vec<u32>],
+    root: [u8; 32],
+) -> (&'a [Vec<u32>], [u8; 32]) {
+    match held_out {
+        Some((rows, root)) => (rows, *root),
+        None => (rows, root),
+    }
+}
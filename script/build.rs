@@ -1,5 +1,44 @@
-use sp1_helper::build_program_with_args;
+use sha2::{Digest, Sha256};
+use sp1_helper::{build_program_with_args, BuildArgs};
+use std::path::PathBuf;
 
 fn main() {
-    build_program_with_args("../program", Default::default())
+    // Build the guests with the same numeric backend as the host.
+    let mut features = Vec::new();
+    if std::env::var_os("CARGO_FEATURE_I16F16").is_some() {
+        features.push("i16f16".to_string());
+    }
+
+    // The training and inference guests are binaries of the `program` crate.
+    for (binary, name) in [("perceptron", "PERCEPTRON"), ("mlp", "MLP"), ("infer", "INFER")] {
+        let elf_name = format!("{}-elf", binary);
+        build_program_with_args(
+            "../program",
+            BuildArgs {
+                binary: binary.to_string(),
+                elf_name: elf_name.clone(),
+                features: features.clone(),
+                ..Default::default()
+            },
+        );
+        export_elf(name, &elf_name);
+    }
+
+    // The aggregation guest verifies proofs of the training guests, so it is a crate of its own.
+    build_program_with_args(
+        "../aggregation",
+        BuildArgs { elf_name: "aggregation-elf".to_string(), ..Default::default() },
+    );
+    export_elf("AGGREGATION", "aggregation-elf");
+}
+
+/// Export the path and SHA-256 digest of an ELF built into the `elf` directory of the workspace,
+/// which the binaries embed and check at startup.
+fn export_elf(name: &str, elf_name: &str) {
+    let dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("../elf");
+    let path = dir.join(elf_name).canonicalize().unwrap_or_else(|e| panic!("missing ELF {}: {}", elf_name, e));
+    let elf = std::fs::read(&path).unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e));
+
+    println!("cargo:rustc-env={}_ELF_PATH={}", name, path.display());
+    println!("cargo:rustc-env={}_ELF_SHA256={}", name, hex::encode(Sha256::digest(&elf)));
 }
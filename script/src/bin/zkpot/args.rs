@@ -0,0 +1,645 @@
+//! The flags of a training run, shared by every subcommand that trains or runs a model, and the
+//! loader that turns them into a [`TrainingRun`]. The flags serialize back into a config table,
+//! which is saved next to proofs so that their runs can be reproduced with `--config`.
+
+use clap::{Args, ValueEnum};
+use fibonacci_lib::optim::{Adam, AnyOptimizer, Momentum, Sgd};
+use fibonacci_lib::{
+    ActivationKind, Checkpoint, Commitment, DatasetId, Fixed, GuestMode, LayerSpec, LossKind, MerkleTree,
+    NumericType, Scalar, Schedule, Sequential, TrainingState, EXTERNAL_DATASET,
+};
+use fibonacci_script::{checkpoint, mnist, program::Program, training::TrainingRun};
+use serde::{Serialize, Serializer};
+use std::path::{Path, PathBuf};
+
+use crate::check::print_training;
+
+/// The flags of a training run. They can also be read from a file with `--config`.
+///
+/// Only the flags that describe the run are serialized, not the ones that read or write other
+/// files or print extra output.
+#[derive(Args, Debug, Serialize)]
+pub struct TrainArgs {
+    /// The built-in dataset to train on: and, nand, or, nor, xor or mlp-test. Defaults to nand,
+    /// or to mlp-test with `--model mlp`.
+    #[clap(long, conflicts_with_all = ["data", "mnist"])]
+    #[serde(serialize_with = "serialize_dataset")]
+    dataset: Option<DatasetId>,
+
+    #[clap(long, default_value = "10")]
+    t: u32,
+
+    /// The learning rate, in (0, 1].
+    #[clap(long, default_value = "0.1", value_parser = parse_learning_rate)]
+    r: f32,
+
+    /// The loss to train with: mse, or bce or softmax-ce, which read the outputs as logits. The
+    /// perceptron only trains with mse.
+    #[clap(long, value_enum, default_value = "mse")]
+    loss: LossFunction,
+
+    /// The optimizer: sgd, momentum or adam.
+    #[clap(long, value_enum, default_value = "sgd")]
+    optimizer: Optimizer,
+
+    /// The number of consecutive examples whose gradients are averaged into one optimizer step.
+    #[clap(long, default_value = "1", value_parser = parse_batch_size)]
+    batch_size: u32,
+
+    /// The momentum coefficient of `--optimizer momentum`.
+    #[clap(long, default_value = "0.9", value_parser = parse_fraction)]
+    momentum: f32,
+
+    /// The decay rate of the first moment estimate of `--optimizer adam`.
+    #[clap(long, default_value = "0.9", value_parser = parse_fraction)]
+    beta1: f32,
+
+    /// The decay rate of the second moment estimate of `--optimizer adam`.
+    #[clap(long, default_value = "0.999", value_parser = parse_fraction)]
+    beta2: f32,
+
+    /// The term added to the denominator of `--optimizer adam`. Defaults to 1e-8 in `f32` and to
+    /// 1e-2 in 16.16 fixed point, where small second moments round to zero.
+    #[clap(long, value_parser = parse_epsilon)]
+    epsilon: Option<f32>,
+
+    /// How the learning rate changes over the epochs: constant, step or cosine.
+    #[clap(long, value_enum, default_value = "constant")]
+    schedule: LrSchedule,
+
+    /// The epochs between two decays of `--schedule step`, or the epochs over which `--schedule
+    /// cosine` decays the learning rate.
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u32).range(1..))]
+    schedule_epochs: u32,
+
+    /// The factor `--schedule step` multiplies the learning rate by, or the fraction of the
+    /// learning rate `--schedule cosine` ends at.
+    #[clap(long, default_value = "0.5", value_parser = parse_fraction)]
+    schedule_factor: f32,
+
+    /// Start a fresh optimizer state from the loss and optimizer flags instead of resuming the one
+    /// saved in `--weights-file`.
+    #[clap(long, requires = "weights_file")]
+    reset_optimizer: bool,
+
+    /// The initial weight of the left input of the perceptron.
+    #[clap(long, default_value = "0.1", allow_hyphen_values = true, value_parser = parse_weight)]
+    w_l: f32,
+
+    /// The initial weight of the right input of the perceptron.
+    #[clap(long, default_value = "-0.1", allow_hyphen_values = true, value_parser = parse_weight)]
+    w_r: f32,
+
+    /// The initial bias of the perceptron.
+    #[clap(long, default_value = "0", allow_hyphen_values = true, value_parser = parse_weight)]
+    b: f32,
+
+    /// Start from the checkpoint in this file instead of `--w-l`/`--w-r` or the seeded weights,
+    /// and resume its optimizer state. With `--input`, predict with it. Files ending in `.json`
+    /// hold JSON, others the binary encoding.
+    #[clap(long)]
+    weights_file: Option<PathBuf>,
+
+    /// Read flags from this JSON or TOML file. Flags on the command line take precedence.
+    #[clap(long)]
+    #[serde(skip)]
+    config: Option<PathBuf>,
+
+    /// The guest program to run: perceptron or mlp train `--model`, infer predicts on `--input`
+    /// and aggregate proves training in segments of `--epochs-per-proof`. Defaults to the one the
+    /// other flags ask for.
+    #[clap(long, value_enum)]
+    program: Option<Program>,
+
+    #[clap(long, value_enum, default_value = "perceptron")]
+    pub model: Model,
+
+    /// Layers of the sequential model as `input:output:activation`, separated by commas. The
+    /// activation is one of identity, relu, leaky-relu, step or sigmoid.
+    #[clap(long, value_delimiter = ',', value_parser = parse_layer, default_value = "2:4:relu,4:1:identity")]
+    #[serde(serialize_with = "serialize_layers")]
+    layers: Vec<LayerSpec>,
+
+    /// Width of the hidden layer of the MLP.
+    #[clap(long, default_value = "4")]
+    hidden: u32,
+
+    /// The number of targets at the end of each example, which is the output width of the MLP.
+    /// Its input width is the rest of the example. Defaults to the targets of the built-in
+    /// dataset, to 10 classes for `--mnist` and to 3 for `--data`.
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    outputs: Option<u32>,
+
+    /// Seed for the initial parameters of the sequential model and the MLP.
+    #[clap(long, default_value = "1")]
+    seed: u64,
+
+    /// Predict on this input, as decimals separated by commas, instead of training.
+    #[clap(long, value_delimiter = ',', allow_hyphen_values = true)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub input: Vec<f32>,
+
+    /// Keep the weights and the dataset private and commit only their SHA-256 digests.
+    #[clap(long)]
+    pub private: bool,
+
+    /// Write a checkpoint of the trained weights and optimizer state to this file, so its digest
+    /// can be checked later and training can be resumed from it.
+    #[clap(long)]
+    #[serde(skip)]
+    pub weights_out: Option<PathBuf>,
+
+    /// Check that the checkpoint in this file matches one of the committed digests.
+    #[clap(long, requires = "private")]
+    #[serde(skip)]
+    pub check_weights: Option<PathBuf>,
+
+    /// Train on the examples in this JSON file, an array of rows holding the inputs followed by
+    /// the targets, instead of a built-in dataset.
+    #[clap(long)]
+    data: Option<PathBuf>,
+
+    /// Train on images from an MNIST parquet file, see `download_mnist.sh`.
+    #[clap(long, conflicts_with = "data")]
+    mnist: Option<PathBuf>,
+
+    /// Evaluate the trained model on this built-in dataset instead of the training set.
+    #[clap(long)]
+    #[serde(serialize_with = "serialize_dataset")]
+    eval_dataset: Option<DatasetId>,
+
+    /// Evaluate the trained model on the examples in this JSON file instead of the training set.
+    #[clap(long, conflicts_with = "eval_dataset")]
+    eval_data: Option<PathBuf>,
+
+    /// Number of MNIST images to train on.
+    #[clap(long, default_value = "100")]
+    mnist_limit: usize,
+
+    /// Shrink MNIST images by this factor in each direction.
+    #[clap(long, default_value = "4")]
+    mnist_downscale: usize,
+
+    /// Print a Merkle inclusion proof for the example at this index.
+    #[clap(long)]
+    #[serde(skip)]
+    inclusion: Option<usize>,
+
+    /// Prove training in segments of this many epochs, then aggregate the segment proofs.
+    #[clap(long, requires = "private", value_parser = clap::value_parser!(u32).range(1..))]
+    pub epochs_per_proof: Option<u32>,
+
+    /// Save segment proofs in this directory, and reuse the ones an earlier run saved there.
+    #[clap(long, requires = "epochs_per_proof")]
+    #[serde(skip)]
+    pub segments_dir: Option<PathBuf>,
+}
+
+/// Enum representing the models the guest can train.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Model {
+    Perceptron,
+    Sequential,
+    Mlp,
+}
+
+/// Enum representing the losses the guest can train with.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum LossFunction {
+    Mse,
+    Bce,
+    SoftmaxCe,
+}
+
+/// Enum representing the optimizers the guest can train with.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Optimizer {
+    Sgd,
+    Momentum,
+    Adam,
+}
+
+/// Enum representing the learning rate schedules.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum LrSchedule {
+    Constant,
+    Step,
+    Cosine,
+}
+
+impl Model {
+    /// The guest mode that trains this model.
+    pub fn guest_mode(self) -> GuestMode {
+        match self {
+            Model::Perceptron => GuestMode::Perceptron,
+            Model::Sequential => GuestMode::Sequential,
+            Model::Mlp => GuestMode::Mlp,
+        }
+    }
+}
+
+impl TrainArgs {
+    /// The guest program the flags ask for, exiting with an error if `--program` names another.
+    pub fn program(&self) -> Program {
+        let asked = if !self.input.is_empty() {
+            Program::Infer
+        } else if self.epochs_per_proof.is_some() {
+            Program::Aggregate
+        } else {
+            Program::for_mode(self.model.guest_mode())
+        };
+
+        let program = self.program.unwrap_or(asked);
+        if program != asked {
+            let needs = match program {
+                Program::Perceptron => "--model perceptron",
+                Program::Mlp => "--model sequential or --model mlp",
+                Program::Infer => "--input",
+                Program::Aggregate => "--private --epochs-per-proof",
+            };
+            let (program, asked) = (program.name(), asked.name());
+            eprintln!("Error: --program {} needs {}, these flags run --program {}", program, needs, asked);
+            std::process::exit(1);
+        }
+
+        program
+    }
+
+    /// The built-in dataset to train on when no file is given.
+    fn dataset(&self) -> DatasetId {
+        let default = if self.model == Model::Mlp { DatasetId::MlpTest } else { DatasetId::Nand };
+        self.dataset.unwrap_or(default)
+    }
+
+    /// How the guest commits to the weights and the dataset.
+    pub fn commitment(&self) -> Commitment {
+        if self.private {
+            Commitment::Digest
+        } else {
+            Commitment::Clear
+        }
+    }
+
+    /// Load the dataset, the initial weights and the training state the flags describe, exiting
+    /// with an error if they do not fit together.
+    pub fn load(&self) -> TrainingRun {
+        if self.epochs_per_proof.is_some() && self.t == 0 {
+            eprintln!("Error: --epochs-per-proof needs at least one epoch to split, got --t 0");
+            std::process::exit(1);
+        }
+        if self.model == Model::Perceptron && self.loss != LossFunction::Mse {
+            eprintln!("Error: The perceptron only trains with --loss mse");
+            std::process::exit(1);
+        }
+        if let Some(path) = &self.config {
+            println!("config: {}", path.display());
+        }
+
+        // Load the dataset and commit to it the same way the guest does.
+        let (d, rows) = match (&self.data, &self.mnist) {
+            (Some(path), _) => (EXTERNAL_DATASET, load_examples(path)),
+            (_, Some(path)) => (EXTERNAL_DATASET, self.load_mnist(path)),
+            _ => {
+                let dataset = self.dataset();
+                println!("dataset: {}", dataset);
+                (dataset.to_u32(), dataset.rows())
+            }
+        };
+        let tree = MerkleTree::new(&rows);
+        println!("dataset root: 0x{}", hex::encode(tree.root()));
+
+        if let Some(index) = self.inclusion {
+            if index >= tree.num_leaves() {
+                eprintln!("Error: --inclusion {} is out of range for {} examples", index, tree.num_leaves());
+                std::process::exit(1);
+            }
+            let proof = tree.inclusion_proof(index);
+            println!("example {}: {:?}", index, rows[index]);
+            for sibling in &proof.siblings {
+                println!("  sibling: 0x{}", hex::encode(sibling));
+            }
+            assert!(proof.verify(&tree.root(), &rows[index]));
+            println!("Inclusion proof is valid.");
+        }
+
+        // Setup the inputs, encoded in the lib's numeric type.
+        let (architecture, params) = match self.model {
+            Model::Perceptron => {
+                (vec![], [self.w_l, self.w_r, self.b].iter().map(|&w| Fixed::from_f32(w).to_raw()).collect())
+            }
+            Model::Sequential => {
+                if !LayerSpec::connected(&self.layers) {
+                    let layers = self.layers.iter().map(format_layer).collect::<Vec<_>>().join(",");
+                    eprintln!("Error: --layers {:?} must be one or more layers, each fed by the one before", layers);
+                    std::process::exit(1);
+                }
+                if LayerSpec::total_params(&self.layers).is_none() {
+                    eprintln!("Error: --layers {:?} has too many parameters", self.layers);
+                    std::process::exit(1);
+                }
+                let layers = LayerSpec::encode_all(&self.layers);
+                let params: Vec<u32> = Sequential::<Fixed>::seeded(&self.layers, self.seed)
+                    .params()
+                    .into_iter()
+                    .map(Fixed::to_raw)
+                    .collect();
+
+                println!("layers: {:?}", self.layers);
+
+                (layers, params)
+            }
+            Model::Mlp => {
+                // The inputs are the values of an example before its targets, which are one-hot
+                // classes for MNIST.
+                let outputs = self.outputs.unwrap_or(match (&self.data, &self.mnist) {
+                    (Some(_), _) => 3,
+                    (_, Some(_)) => mnist::CLASSES as u32,
+                    _ => self.dataset().targets() as u32,
+                });
+                let width = rows.first().map_or(0, Vec::len) as u32;
+                if width <= outputs {
+                    eprintln!("Error: the examples have {} values, too few for inputs and {} outputs", width, outputs);
+                    std::process::exit(1);
+                }
+                let shape = [width - outputs, self.hidden, outputs];
+                let specs = [
+                    LayerSpec::new(shape[0], shape[1], ActivationKind::Relu),
+                    LayerSpec::new(shape[1], shape[2], ActivationKind::Identity),
+                ];
+                let params: Vec<u32> = Sequential::<Fixed>::seeded(&specs, self.seed)
+                    .params()
+                    .into_iter()
+                    .map(Fixed::to_raw)
+                    .collect();
+
+                println!("shape: {:?}", shape);
+
+                (shape.to_vec(), params)
+            }
+        };
+
+        // Replace the initial weights by the ones in the checkpoint, which must hold the same
+        // model, and resume its optimizer state unless asked to start a fresh one.
+        let training = self.training_state(params.len());
+        let (params, training) = match &self.weights_file {
+            Some(path) => {
+                let checkpoint = read_checkpoint(path);
+                if let Err(e) = checkpoint.check(self.model.guest_mode()) {
+                    eprintln!("Error: {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+                if checkpoint.architecture() != architecture {
+                    eprintln!(
+                        "Error: {} holds architecture {:?}, but the model has {:?}",
+                        path.display(),
+                        checkpoint.architecture(),
+                        architecture
+                    );
+                    std::process::exit(1);
+                }
+                let training = if self.reset_optimizer { training } else { checkpoint.training() };
+                if Fixed::from_count(training.batch_size).is_none() {
+                    eprintln!(
+                        "Error: {} resumes batches of {} examples, more than the numeric type can average over",
+                        path.display(),
+                        training.batch_size
+                    );
+                    std::process::exit(1);
+                }
+                (checkpoint.params().to_vec(), training)
+            }
+            None => (params, training),
+        };
+
+        // Load the held-out set, which is committed through its Merkle root like the training set.
+        let held_out = match (&self.eval_data, self.eval_dataset) {
+            (Some(path), _) => Some((EXTERNAL_DATASET, load_examples(path))),
+            (_, Some(dataset)) => Some((dataset.to_u32(), dataset.rows())),
+            _ => None,
+        };
+        if let Some((_, rows)) = &held_out {
+            println!("held-out root: 0x{}", hex::encode(MerkleTree::new(rows).root()));
+        }
+
+        // Check that every example fits the model before the guest reads them.
+        let width = match self.model {
+            Model::Perceptron => 3,
+            Model::Sequential => (self.layers[0].input + self.layers[self.layers.len() - 1].output) as usize,
+            Model::Mlp => (architecture[0] + architecture[2]) as usize,
+        };
+        for (name, rows) in [("training", Some(&rows)), ("held-out", held_out.as_ref().map(|(_, rows)| rows))] {
+            let Some(rows) = rows else { continue };
+            if let Some((i, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != width) {
+                eprintln!(
+                    "Error: {} example {} has {} values, but the model takes {} inputs and targets",
+                    name,
+                    i,
+                    row.len(),
+                    width
+                );
+                std::process::exit(1);
+            }
+        }
+
+        // The evaluation set is averaged over, which the fixed-point backend can only do for as many
+        // examples as it can count.
+        let evaluated = held_out.as_ref().map_or(&rows, |(_, rows)| rows);
+        if u32::try_from(evaluated.len()).ok().and_then(Fixed::from_count).is_none() {
+            eprintln!(
+                "Error: the evaluation set has {} examples, more than the numeric type can average over",
+                evaluated.len()
+            );
+            std::process::exit(1);
+        }
+
+        let r = Fixed::from_f32(self.r).to_raw();
+        TrainingRun { mode: self.model.guest_mode(), architecture, d, rows, t: self.t, r, params, training, held_out }
+    }
+
+    /// Print the inputs of a run loaded with [`TrainArgs::load`].
+    pub fn print(&self, run: &TrainingRun) {
+        println!("d: {}", run.d);
+        println!("t: {}", run.t);
+        println!("r: {}", self.r);
+        match self.model {
+            Model::Perceptron => {
+                println!("w_l: {}", Fixed::from_raw(run.params[0]).to_f32());
+                println!("w_r: {}", Fixed::from_raw(run.params[1]).to_f32());
+                println!("b: {}", Fixed::from_raw(run.params[2]).to_f32());
+            }
+            _ => println!("params: {}", run.params.len()),
+        }
+        print_training(&run.training);
+    }
+
+    /// The training state of a fresh run, from the loss and optimizer flags.
+    fn training_state(&self, num_params: usize) -> TrainingState<Fixed> {
+        let fixed = Fixed::from_f32;
+        let loss = match self.loss {
+            LossFunction::Mse => LossKind::Mse,
+            LossFunction::Bce => LossKind::BinaryCrossEntropy,
+            LossFunction::SoftmaxCe => LossKind::SoftmaxCrossEntropy,
+        };
+        let optimizer = match self.optimizer {
+            Optimizer::Sgd => AnyOptimizer::Sgd(Sgd),
+            Optimizer::Momentum => AnyOptimizer::Momentum(Momentum::new(fixed(self.momentum), num_params)),
+            Optimizer::Adam => {
+                let epsilon = self.epsilon.unwrap_or(match NumericType::FIXED {
+                    NumericType::F32 => 1e-8,
+                    NumericType::Q16 => 1e-2,
+                });
+                AnyOptimizer::Adam(Adam::new(fixed(self.beta1), fixed(self.beta2), fixed(epsilon), num_params))
+            }
+        };
+        let schedule = match self.schedule {
+            LrSchedule::Constant => Schedule::Constant,
+            LrSchedule::Step => {
+                Schedule::StepDecay { every: self.schedule_epochs, factor: fixed(self.schedule_factor) }
+            }
+            LrSchedule::Cosine => Schedule::Cosine { epochs: self.schedule_epochs, min: fixed(self.schedule_factor) },
+        };
+
+        TrainingState { loss, optimizer, schedule, batch_size: self.batch_size, epoch: 0 }
+    }
+
+    /// Load, subsample and downscale MNIST images into raw examples.
+    fn load_mnist(&self, path: &Path) -> Vec<Vec<u32>> {
+        if self.mnist_downscale == 0 {
+            eprintln!("Error: --mnist-downscale must be positive");
+            std::process::exit(1);
+        }
+        let images = mnist::load(path).unwrap_or_else(|e| {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        });
+        let images: Vec<_> = mnist::subsample(images, self.mnist_limit, self.seed)
+            .iter()
+            .map(|image| image.downscale(self.mnist_downscale))
+            .collect();
+        let Some(first) = images.first() else {
+            eprintln!("Error: {} has no images to train on", path.display());
+            std::process::exit(1);
+        };
+        println!("Loaded {} MNIST images of {}x{}", images.len(), first.width, first.height);
+
+        mnist::to_rows(&images)
+    }
+}
+
+/// The largest initial weight magnitude, which keeps training well inside the range of 16.16
+/// fixed point.
+const MAX_WEIGHT: f32 = 256.0;
+
+fn parse_learning_rate(s: &str) -> Result<f32, String> {
+    let r: f32 = s.parse().map_err(|e| format!("invalid learning rate `{s}`: {e}"))?;
+    if !(r > 0.0 && r <= 1.0) {
+        return Err(format!("learning rate {r} is not in (0, 1]"));
+    }
+
+    Ok(r)
+}
+
+fn parse_fraction(s: &str) -> Result<f32, String> {
+    let f: f32 = s.parse().map_err(|e| format!("invalid fraction `{s}`: {e}"))?;
+    if !(0.0..1.0).contains(&f) {
+        return Err(format!("{f} is not in [0, 1)"));
+    }
+
+    Ok(f)
+}
+
+fn parse_epsilon(s: &str) -> Result<f32, String> {
+    let epsilon: f32 = s.parse().map_err(|e| format!("invalid epsilon `{s}`: {e}"))?;
+    if !epsilon.is_finite() || Fixed::from_f32(epsilon) <= Fixed::ZERO {
+        return Err(format!("epsilon {epsilon} is not positive in the numeric type"));
+    }
+
+    Ok(epsilon)
+}
+
+fn parse_batch_size(s: &str) -> Result<u32, String> {
+    let size: u32 = s.parse().map_err(|e| format!("invalid batch size `{s}`: {e}"))?;
+    if size == 0 {
+        return Err("batch size must be positive".to_string());
+    }
+    if Fixed::from_count(size).is_none() {
+        return Err(format!("batch size {size} is more than the numeric type can average over"));
+    }
+
+    Ok(size)
+}
+
+fn parse_weight(s: &str) -> Result<f32, String> {
+    let w: f32 = s.parse().map_err(|e| format!("invalid weight `{s}`: {e}"))?;
+    if !w.is_finite() || w.abs() > MAX_WEIGHT {
+        return Err(format!("weight {w} is not in [-{MAX_WEIGHT}, {MAX_WEIGHT}]"));
+    }
+
+    Ok(w)
+}
+
+/// The names of the activations in layer specs.
+const ACTIVATIONS: [(&str, ActivationKind); 5] = [
+    ("identity", ActivationKind::Identity),
+    ("relu", ActivationKind::Relu),
+    ("step", ActivationKind::Step),
+    ("leaky-relu", ActivationKind::LeakyRelu),
+    ("sigmoid", ActivationKind::Sigmoid),
+];
+
+/// Parse a layer spec such as `4:3:relu`.
+fn parse_layer(s: &str) -> Result<LayerSpec, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    let [input, output, activation] = parts[..] else {
+        return Err(format!("expected input:output:activation, got `{s}`"));
+    };
+
+    let input = input.parse().map_err(|e| format!("invalid input width `{input}`: {e}"))?;
+    let output = output.parse().map_err(|e| format!("invalid output width `{output}`: {e}"))?;
+    let Some(&(_, activation)) = ACTIVATIONS.iter().find(|(name, _)| *name == activation) else {
+        return Err(format!("unknown activation `{activation}`"));
+    };
+
+    Ok(LayerSpec::new(input, output, activation))
+}
+
+/// Format a layer spec the way [`parse_layer`] parses it.
+fn format_layer(spec: &LayerSpec) -> String {
+    let (name, _) = ACTIVATIONS.iter().find(|(_, kind)| *kind == spec.activation).expect("every activation has a name");
+    format!("{}:{}:{}", spec.input, spec.output, name)
+}
+
+fn serialize_layers<S: Serializer>(layers: &[LayerSpec], serializer: S) -> Result<S::Ok, S::Error> {
+    layers.iter().map(format_layer).collect::<Vec<_>>().serialize(serializer)
+}
+
+fn serialize_dataset<S: Serializer>(value: &Option<DatasetId>, serializer: S) -> Result<S::Ok, S::Error> {
+    value.map(|dataset| dataset.to_string()).serialize(serializer)
+}
+
+/// Load examples from a JSON array of rows and encode them in the lib's numeric type, exiting with
+/// an error if the file cannot be read or parsed.
+fn load_examples(path: &Path) -> Vec<Vec<u32>> {
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Error: failed to read {}: {}", path.display(), e);
+        std::process::exit(1);
+    });
+    let rows: Vec<Vec<f32>> = serde_json::from_str(&contents).unwrap_or_else(|e| {
+        eprintln!("Error: {} is not a JSON array of rows of numbers: {}", path.display(), e);
+        std::process::exit(1);
+    });
+
+    rows.iter().map(|row| row.iter().map(|&v| Fixed::from_f32(v).to_raw()).collect()).collect()
+}
+
+/// Load a checkpoint, exiting with an error if it cannot be read or its digest does not match.
+pub fn read_checkpoint(path: &Path) -> Checkpoint {
+    checkpoint::load(path).unwrap_or_else(|e| {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    })
+}
@@ -0,0 +1,139 @@
+//! Decoding and printing the public values of the guest, and checking them against the native
+//! library call on the same inputs.
+
+use alloy_sol_types::SolType;
+use fibonacci_lib::{
+    input_digest, predict, Checkpoint, Commitment, DigestPublicValuesStruct, EvaluationStruct, Fixed, GuestMode,
+    InferencePublicValuesStruct, MerkleTree, MlpPublicValuesStruct, OptimizerStruct, PublicValuesStruct,
+    SequentialPublicValuesStruct, TrainingState,
+};
+use fibonacci_script::claim::{decode_params, print_evaluation, print_mlp, print_perceptron, print_sequential};
+use fibonacci_script::training::TrainingRun;
+use std::path::Path;
+
+use crate::args::read_checkpoint;
+
+/// Check the public values of a training run against training natively.
+pub fn check_training(output: &[u8], commitment: Commitment, run: &TrainingRun) {
+    match (commitment, run.mode) {
+        (Commitment::Clear, GuestMode::Perceptron) => check_perceptron(output, run),
+        (Commitment::Clear, GuestMode::Sequential) => check_sequential(output, run),
+        (Commitment::Clear, GuestMode::Mlp) => check_mlp(output, run),
+        (Commitment::Digest, _) => check_digests(output, run, &run.train_native()),
+        (_, GuestMode::Infer) => panic!("inference is not a training run"),
+    }
+}
+
+pub fn check_perceptron(output: &[u8], run: &TrainingRun) {
+    let decoded = PublicValuesStruct::abi_decode(output, true).unwrap();
+    print_perceptron(&decoded);
+
+    let PublicValuesStruct {
+        d,
+        dataset_root,
+        t,
+        w_l,
+        w_r,
+        b,
+        r,
+        updated_w_l,
+        updated_w_r,
+        updated_b,
+        optimizer,
+        evaluation,
+    } = decoded;
+    assert_eq!(dataset_root, MerkleTree::new(&run.rows).root());
+    assert_eq!((d, t, r, vec![w_l, w_r, b]), (run.d, run.t, run.r, run.params.clone()));
+    check_trained(run, &[updated_w_l, updated_w_r, updated_b], &optimizer, &evaluation);
+}
+
+fn check_sequential(output: &[u8], run: &TrainingRun) {
+    let decoded = SequentialPublicValuesStruct::abi_decode(output, true).unwrap();
+    print_sequential(&decoded);
+
+    let SequentialPublicValuesStruct { layers, d, dataset_root, t, r, params, updated_params, optimizer, evaluation } =
+        decoded;
+    assert_eq!(dataset_root, MerkleTree::new(&run.rows).root());
+    assert_eq!((layers, d, t, r, params), (run.architecture.clone(), run.d, run.t, run.r, run.params.clone()));
+    check_trained(run, &updated_params, &optimizer, &evaluation);
+}
+
+fn check_mlp(output: &[u8], run: &TrainingRun) {
+    let decoded = MlpPublicValuesStruct::abi_decode(output, true).unwrap();
+    print_mlp(&decoded);
+
+    let MlpPublicValuesStruct { shape, d, dataset_root, t, r, params, updated_params, optimizer, evaluation } =
+        decoded;
+    assert_eq!(dataset_root, MerkleTree::new(&run.rows).root());
+    assert_eq!((shape, d, t, r, params), (run.architecture.clone(), run.d, run.t, run.r, run.params.clone()));
+    check_trained(run, &updated_params, &optimizer, &evaluation);
+}
+
+/// Check the trained weights, the optimizer and the evaluation committed in the clear against
+/// training natively.
+fn check_trained(
+    run: &TrainingRun,
+    updated_params: &[u32],
+    optimizer: &OptimizerStruct,
+    evaluation: &EvaluationStruct,
+) {
+    let trained = run.train_native();
+    assert_eq!(updated_params, trained.params());
+    assert_eq!(*optimizer, OptimizerStruct::new(&run.training, &trained.training()));
+    assert_eq!(*evaluation, run.evaluate_native(updated_params));
+}
+
+/// Check the digests and the evaluation committed by a run of `run`, which trained the checkpoint
+/// of `run` into `trained`.
+pub fn check_digests(output: &[u8], run: &TrainingRun, trained: &Checkpoint) {
+    let decoded = DigestPublicValuesStruct::abi_decode(output, true).unwrap();
+
+    println!("dataset_root: {}", decoded.dataset_root);
+    println!("weights_digest: {}", decoded.weights_digest);
+    println!("updated_weights_digest: {}", decoded.updated_weights_digest);
+    print_evaluation(&decoded.evaluation);
+
+    assert_eq!(decoded.dataset_root, MerkleTree::new(&run.rows).root());
+    assert_eq!(decoded.weights_digest, run.checkpoint().digest());
+    assert_eq!(decoded.updated_weights_digest, trained.digest());
+    assert_eq!(decoded.evaluation, run.evaluate_native(trained.params()));
+}
+
+/// Compare the digest of a checkpoint against the committed initial and final checkpoints.
+pub fn check_weights_file(path: &Path, output: &[u8]) {
+    let digest = read_checkpoint(path).digest();
+    let decoded = DigestPublicValuesStruct::abi_decode(output, true).unwrap();
+
+    if decoded.updated_weights_digest == digest {
+        println!("{} matches the committed trained weights.", path.display());
+    } else if decoded.weights_digest == digest {
+        println!("{} matches the committed initial weights.", path.display());
+    } else {
+        eprintln!("Error: {} does not match any committed checkpoint digest", path.display());
+        std::process::exit(1);
+    }
+}
+
+pub fn check_inference(output: &[u8], checkpoint: &Checkpoint, input: &[u32]) {
+    let decoded = InferencePublicValuesStruct::abi_decode(output, true).unwrap();
+    let outputs = decode_params(&decoded.output);
+
+    println!("weights_digest: {}", decoded.weights_digest);
+    println!("input_digest: {}", decoded.input_digest);
+    println!("output: {:?}", outputs);
+
+    let (model, architecture, params) = (checkpoint.model(), checkpoint.architecture(), checkpoint.params());
+    assert_eq!(decoded.model, model.to_u32());
+    assert_eq!(decoded.weights_digest, checkpoint.digest());
+    assert_eq!(decoded.input_digest, input_digest(input));
+    assert_eq!(decoded.output, predict(model, architecture, params, input));
+}
+
+/// Print the loss and optimizer a run starts with.
+pub fn print_training(training: &TrainingState<Fixed>) {
+    println!("loss function: {:?}", training.loss);
+    println!("optimizer: {:?}", training.optimizer.kind());
+    println!("batch size: {}", training.batch_size);
+    println!("schedule: {:?}", training.schedule);
+    println!("epoch: {}", training.epoch);
+}
@@ -0,0 +1,201 @@
+//! EVM-compatible proofs of perceptron training runs, and the fixtures the Solidity tests verify.
+
+use alloy_sol_types::SolType;
+use clap::{Args, ValueEnum};
+use fibonacci_lib::{EvaluationStruct, PublicValuesStruct};
+use fibonacci_script::claim::ClaimKind;
+use fibonacci_script::program::{Inputs, Program};
+use serde::{Deserialize, Serialize};
+use sp1_sdk::{HashableKey, ProverClient, SP1ProofWithPublicValues, SP1VerifyingKey};
+use std::path::PathBuf;
+
+use crate::args::TrainArgs;
+use crate::check::check_perceptron;
+use crate::{save_proof, setup};
+
+/// The arguments of the `fixture` subcommand.
+#[derive(Args, Debug)]
+pub struct FixtureArgs {
+    #[clap(flatten)]
+    train: TrainArgs,
+
+    #[clap(long, value_enum, default_value = "groth16")]
+    system: ProofSystem,
+
+    /// Also write the proof to this file.
+    #[clap(long)]
+    proof: Option<PathBuf>,
+}
+
+/// Enum representing the available proof systems
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum ProofSystem {
+    Plonk,
+    Groth16,
+}
+
+/// A fixture that can be used to test the verification of SP1 zkVM proofs inside Solidity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SP1TrainingProofFixture {
+    d: u32,
+    dataset_root: String,
+    t: u32,
+    r: u32,
+    w_l: u32,
+    w_r: u32,
+    b: u32,
+    updated_w_l: u32,
+    updated_w_r: u32,
+    updated_b: u32,
+    optimizer: OptimizerFixture,
+    evaluation: EvaluationFixture,
+    vkey: String,
+    public_values: String,
+    proof: String,
+}
+
+/// The committed optimizer of the run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OptimizerFixture {
+    kind: u32,
+    batch_size: u32,
+    schedule: u32,
+    schedule_epochs: u32,
+    schedule_factor: u32,
+    beta1: u32,
+    beta2: u32,
+    epsilon: u32,
+    epoch: u32,
+    state_digest: String,
+    updated_state_digest: String,
+}
+
+/// The committed loss and accuracy of the trained weights.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EvaluationFixture {
+    dataset_root: String,
+    loss_function: u32,
+    loss: u32,
+    correct: u32,
+    total: u32,
+}
+
+/// Prove a perceptron training run that commits its weights in the clear with Groth16 or PLONK,
+/// which is what the `ZkPoTVerifier` contract decodes, and write its fixture.
+pub fn fixture(client: &ProverClient, args: &FixtureArgs) {
+    if args.train.program() != Program::Perceptron || args.train.private {
+        eprintln!("Error: Fixtures prove perceptron runs that commit their weights in the clear");
+        std::process::exit(1);
+    }
+
+    // Setup the program.
+    let (pk, vk) = setup(client, Program::Perceptron);
+
+    // Setup the inputs.
+    let run = args.train.load();
+    args.train.print(&run);
+    let inputs = Inputs::Training(&run, args.train.commitment());
+    let stdin = Program::Perceptron.stdin(inputs).expect("inputs of the program");
+    println!("Proof System: {:?}", args.system);
+
+    // Generate the proof based on the selected proof system.
+    let proof = match args.system {
+        ProofSystem::Plonk => client.prove(&pk, stdin).plonk().run(),
+        ProofSystem::Groth16 => client.prove(&pk, stdin).groth16().run(),
+    }
+    .expect("failed to generate proof");
+
+    // Check the trained weights against the native implementation before writing the fixture.
+    check_perceptron(proof.public_values.as_slice(), &run);
+    println!("Values are correct!");
+
+    if let Some(path) = &args.proof {
+        save_proof(&proof, &vk, Program::Perceptron, ClaimKind::Perceptron, &args.train, path);
+    }
+    create_proof_fixture(&proof, &vk, args.system);
+}
+
+/// Create a fixture for the given proof.
+fn create_proof_fixture(
+    proof: &SP1ProofWithPublicValues,
+    vk: &SP1VerifyingKey,
+    system: ProofSystem,
+) {
+    // Deserialize the public values.
+    let bytes = proof.public_values.as_slice();
+    let PublicValuesStruct {
+        d,
+        dataset_root,
+        t,
+        w_l,
+        w_r,
+        b,
+        r,
+        updated_w_l,
+        updated_w_r,
+        updated_b,
+        optimizer,
+        evaluation,
+    } = PublicValuesStruct::abi_decode(bytes, false).unwrap();
+    let EvaluationStruct { dataset_root: eval_root, loss_function, loss, correct, total } = evaluation;
+    let optimizer = OptimizerFixture {
+        kind: optimizer.kind,
+        batch_size: optimizer.batch_size,
+        schedule: optimizer.schedule,
+        schedule_epochs: optimizer.schedule_epochs,
+        schedule_factor: optimizer.schedule_factor,
+        beta1: optimizer.beta1,
+        beta2: optimizer.beta2,
+        epsilon: optimizer.epsilon,
+        epoch: optimizer.epoch,
+        state_digest: optimizer.state_digest.to_string(),
+        updated_state_digest: optimizer.updated_state_digest.to_string(),
+    };
+
+    // Create the testing fixture so we can test things end-to-end.
+    let fixture = SP1TrainingProofFixture {
+        d,
+        dataset_root: dataset_root.to_string(),
+        t,
+        r,
+        w_l,
+        w_r,
+        b,
+        updated_w_l,
+        updated_w_r,
+        updated_b,
+        optimizer,
+        evaluation: EvaluationFixture { dataset_root: eval_root.to_string(), loss_function, loss, correct, total },
+        vkey: vk.bytes32().to_string(),
+        public_values: format!("0x{}", hex::encode(bytes)),
+        proof: format!("0x{}", hex::encode(proof.bytes())),
+    };
+
+    // The verification key is used to verify that the proof corresponds to the execution of the
+    // program on the given input.
+    //
+    // Note that the verification key stays the same regardless of the input.
+    println!("Verification Key: {}", fixture.vkey);
+
+    // The public values are the values which are publicly committed to by the zkVM.
+    //
+    // If you need to expose the inputs or outputs of your program, you should commit them in
+    // the public values.
+    println!("Public Values: {}", fixture.public_values);
+
+    // The proof proves to the verifier that the program was executed with some inputs that led to
+    // the give public values.
+    println!("Proof Bytes: {}", fixture.proof);
+
+    // Save the fixture to a file.
+    let fixture_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../contracts/src/fixtures");
+    std::fs::create_dir_all(&fixture_path).expect("failed to create fixture path");
+    std::fs::write(
+        fixture_path.join(format!("{:?}-fixture.json", system).to_lowercase()),
+        serde_json::to_string_pretty(&fixture).unwrap(),
+    )
+    .expect("failed to write fixture");
+}
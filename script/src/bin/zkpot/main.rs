@@ -0,0 +1,455 @@
+//! The command line interface of zkPoT, an end-to-end example of using the SP1 SDK to prove the
+//! training of a model. Proofs are written to disk next to a JSON sidecar of what they claim, so
+//! they can be generated on one machine and verified on another.
+//!
+//! You can run this script using the following commands:
+//! ```shell
+//! RUST_LOG=info cargo run --release -- execute
+//! RUST_LOG=info cargo run --release -- prove --proof proof.bin
+//! RUST_LOG=info cargo run --release -- verify --proof proof.bin
+//! ```
+//! or, to train a dense network instead of the perceptron (`--model mlp` trains the 4-h-3 MLP)
+//! ```shell
+//! RUST_LOG=info cargo run --release -- execute --model sequential --layers 2:4:relu,4:1:identity
+//! ```
+//! `execute`, `prove`, `fixture` and `infer` take the same training flags, which can also be read
+//! from a JSON or TOML file with `--config run.toml`, see the `config` module.
+//!
+//! Each guest program is a separate ELF, see the `program` module. `execute` and `prove` run the
+//! one the flags ask for, or check that it is `--program perceptron|mlp|infer|aggregate`, and
+//! `verify` and `vkey` take the same `--program`.
+//!
+//! Pass `--data examples.json` to train on your own examples instead of a built-in dataset, or
+//! `--mnist data/train.parquet` to train on a subsample of MNIST.
+//!
+//! `execute --input 1,0`, or `infer --input 1,0`, executes a prediction instead of training, from
+//! the weights in `--weights-file` or the initial weights of `--model`, and `prove --input 1,0` or
+//! `infer --prove` proves it.
+//!
+//! `--optimizer momentum` or `--optimizer adam` trains with another optimizer than per-example SGD,
+//! `--batch-size` averages the gradients of several examples per step, and `--schedule step` or
+//! `--schedule cosine` decays the learning rate. The optimizer state is saved in checkpoints, so a
+//! run started from `--weights-file` resumes it.
+//!
+//! `--loss bce` or `--loss softmax-ce` trains a classifier with a cross-entropy instead of the
+//! squared error.
+//!
+//! The trained model is evaluated on the training set, or on `--eval-dataset` or `--eval-data`,
+//! and its loss and accuracy are committed.
+//!
+//! With `--private --epochs-per-proof k`, each proof covers `k` epochs and the segment proofs are
+//! aggregated into a single proof by the aggregation program.
+//!
+//! `fixture --system groth16` proves a perceptron run with an EVM-compatible proof and writes the
+//! fixture of the Solidity tests, `vkey` prints the verification key of a program, and `inspect`
+//! prints what a checkpoint or a proof file holds.
+//!
+//! `profile --hidden 2,4,8 --epochs 1,2,4 --examples 8,32` executes the MLP over a sweep of
+//! configurations and fits a cost model of its cycles, see the `profile` module.
+//!
+//! Every subcommand first checks that the embedded ELFs are the ones the build script built, and
+//! every proof is made and verified after printing the digest of its ELF and its verification key.
+//!
+//! `--mock` proves with the mock prover of the SDK, which executes the program and returns proofs
+//! with the real public values but no actual proof, in seconds and without the RAM of the prover.
+
+mod args;
+mod check;
+mod fixture;
+mod profile;
+mod segments;
+
+use args::TrainArgs;
+use check::{check_inference, check_training, check_weights_file, print_training};
+use clap::{Args, Parser, Subcommand};
+use fibonacci_lib::{Fixed, Scalar};
+use fibonacci_script::claim::{check_training_vkey, training_program, Claim, ClaimKind, Sidecar};
+use fibonacci_script::program::{Inputs, Program};
+use fibonacci_script::{checkpoint, config, elf, training::TrainingRun};
+use fixture::FixtureArgs;
+use profile::ProfileArgs;
+use segments::train_in_segments;
+use sp1_sdk::{HashableKey, ProverClient, SP1Proof, SP1ProofWithPublicValues, SP1ProvingKey, SP1VerifyingKey};
+use std::path::{Path, PathBuf};
+
+/// The arguments for the command.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+
+    /// Use the mock prover, whose proofs carry the public values of the execution but prove nothing.
+    #[clap(long, global = true)]
+    mock: bool,
+}
+
+/// Enum representing the subcommands.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Execute a program without proving it, and check it against the library.
+    #[clap(args_override_self = true)]
+    Execute(TrainArgs),
+
+    /// Prove a program, check it against the library and write the proof to disk.
+    #[clap(args_override_self = true)]
+    Prove(ProveArgs),
+
+    /// Verify a proof written to disk against the ELF, and print what it claims.
+    Verify(VerifyArgs),
+
+    /// Print the verification key of a program, the `programVKey` of the Solidity verifier.
+    Vkey(VkeyArgs),
+
+    /// Prove a perceptron training run with Groth16 or PLONK and write a Solidity test fixture.
+    #[clap(args_override_self = true)]
+    Fixture(FixtureArgs),
+
+    /// Execute or prove a prediction of a model on a single input.
+    #[clap(args_override_self = true)]
+    Infer(InferArgs),
+
+    /// Print what a checkpoint or a proof file holds.
+    Inspect(InspectArgs),
+
+    /// Measure the cycles of training the MLP over a sweep, and predict the cost of larger runs.
+    Profile(ProfileArgs),
+}
+
+/// The arguments of the `prove` subcommand.
+#[derive(Args, Debug)]
+struct ProveArgs {
+    #[clap(flatten)]
+    train: TrainArgs,
+
+    /// Write the proof to this file. With `--program aggregate`, the aggregation proof.
+    #[clap(long, default_value = "proof.bin")]
+    proof: PathBuf,
+}
+
+/// The arguments of the `verify` subcommand.
+#[derive(Args, Debug)]
+struct VerifyArgs {
+    /// The proof to verify. Its sidecar is read from the same path with `.json` appended.
+    #[clap(long, default_value = "proof.bin")]
+    proof: PathBuf,
+
+    /// The program the proof must be of. Defaults to the one its sidecar names.
+    #[clap(long, value_enum)]
+    program: Option<Program>,
+}
+
+/// The arguments of the `vkey` subcommand.
+#[derive(Args, Debug)]
+struct VkeyArgs {
+    /// The program whose verification key to print.
+    #[clap(long, value_enum, default_value = "perceptron")]
+    program: Program,
+}
+
+/// The arguments of the `infer` subcommand.
+#[derive(Args, Debug)]
+struct InferArgs {
+    #[clap(flatten)]
+    train: TrainArgs,
+
+    /// Prove the prediction instead of executing it.
+    #[clap(long)]
+    prove: bool,
+
+    /// Write the proof to this file with `--prove`.
+    #[clap(long, default_value = "proof.bin")]
+    proof: PathBuf,
+}
+
+/// The arguments of the `inspect` subcommand.
+#[derive(Args, Debug)]
+struct InspectArgs {
+    /// A checkpoint, or a proof written by `prove`, `infer --prove` or `fixture --proof`.
+    path: PathBuf,
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse the command line arguments, after the ones of the config file.
+    let cli = match config::expand_args(std::env::args().collect()) {
+        Ok(args) => Cli::parse_from(args),
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Check that the embedded guests are the ones of this build.
+    if let Err(e) = elf::check_embedded() {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+
+    // Setup the prover client.
+    let client = if cli.mock {
+        println!("Using the mock prover, proofs will not be sound.");
+        ProverClient::mock()
+    } else {
+        ProverClient::new()
+    };
+
+    match &cli.command {
+        Command::Execute(args) => execute(&client, args),
+        Command::Prove(args) => prove(&client, args, cli.mock),
+        Command::Verify(args) => verify(&client, args),
+        Command::Vkey(args) => vkey(&client, args),
+        Command::Fixture(args) => fixture::fixture(&client, args),
+        Command::Infer(args) => infer(&client, &args.train, args.prove.then_some(args.proof.as_path())),
+        Command::Inspect(args) => inspect(args),
+        Command::Profile(args) => profile::profile(&client, args),
+    }
+}
+
+/// Execute a program and check its public values against running it natively.
+fn execute(client: &ProverClient, args: &TrainArgs) {
+    let program = args.program();
+    if program == Program::Infer {
+        infer(client, args, None);
+        return;
+    }
+
+    let run = args.load();
+    args.print(&run);
+
+    if let Some(k) = args.epochs_per_proof {
+        train_in_segments(client, &run, k, false, None);
+        return;
+    }
+
+    // Execute the program
+    println!("ELF sha256: {}", program.elf_sha256());
+    let stdin = program.stdin(Inputs::Training(&run, args.commitment())).expect("inputs of the program");
+    let (output, report) = client.execute(program.elf(), stdin).run().unwrap();
+    println!("Program executed successfully.");
+
+    // Read the output and check it against the native implementation.
+    check_training(output.as_slice(), args.commitment(), &run);
+    println!("Values are correct!");
+    check_weights(args, &run, output.as_slice());
+
+    // Record the number of cycles executed.
+    println!("Number of cycles: {}", report.total_instruction_count());
+}
+
+/// Prove a program, check its public values against running it natively and write the proof.
+fn prove(client: &ProverClient, args: &ProveArgs, mock: bool) {
+    let program = args.train.program();
+    if program == Program::Infer {
+        infer(client, &args.train, Some(&args.proof));
+        return;
+    }
+
+    let run = args.train.load();
+    args.train.print(&run);
+
+    if let Some(k) = args.train.epochs_per_proof {
+        if mock {
+            eprintln!("Error: the mock prover cannot make the compressed segment proofs that aggregation verifies");
+            std::process::exit(1);
+        }
+        let segments_dir = args.train.segments_dir.as_deref();
+        let (proof, vk) = train_in_segments(client, &run, k, true, segments_dir).expect("aggregation proof");
+        save_proof(&proof, &vk, program, ClaimKind::Aggregate, &args.train, &args.proof);
+        return;
+    }
+
+    // Setup the program for proving.
+    let (pk, vk) = setup(client, program);
+
+    // Generate the proof
+    let stdin = program.stdin(Inputs::Training(&run, args.train.commitment())).expect("inputs of the program");
+    let proof = client.prove(&pk, stdin).run().expect("failed to generate proof");
+
+    println!("Successfully generated proof!");
+
+    // Verify the proof.
+    client.verify(&proof, &vk).expect("failed to verify proof");
+    println!("Successfully verified proof!");
+
+    check_training(proof.public_values.as_slice(), args.train.commitment(), &run);
+    println!("Values are correct!");
+    check_weights(&args.train, &run, proof.public_values.as_slice());
+
+    let kind = ClaimKind::training(run.mode, args.train.commitment());
+    save_proof(&proof, &vk, program, kind, &args.train, &args.proof);
+}
+
+/// Check `--check-weights` against the committed digests, and write the trained weights to
+/// `--weights-out`.
+fn check_weights(args: &TrainArgs, run: &TrainingRun, output: &[u8]) {
+    if let Some(path) = &args.check_weights {
+        check_weights_file(path, output);
+    }
+
+    if let Some(path) = &args.weights_out {
+        checkpoint::save(path, &run.train_native()).expect("failed to write checkpoint");
+        println!("Wrote trained weights to {}", path.display());
+    }
+}
+
+/// Verify a proof written to disk against the ELF, check that it proves the claim in its sidecar,
+/// and print that claim. Exits with a non-zero status if any check fails.
+fn verify(client: &ProverClient, args: &VerifyArgs) {
+    let proof = SP1ProofWithPublicValues::load(&args.proof).unwrap_or_else(|e| {
+        eprintln!("Error: failed to load {}: {:#}", args.proof.display(), e);
+        std::process::exit(1);
+    });
+    let sidecar = Sidecar::load(&args.proof).unwrap_or_else(|e| {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    });
+
+    // The proof must be of the program built into this binary.
+    let program = args.program.unwrap_or(sidecar.program);
+    if program != sidecar.program {
+        eprintln!("Error: the proof is of the {} program, not {}", sidecar.program.name(), program.name());
+        std::process::exit(1);
+    }
+    let (_, vk) = setup(client, program);
+    if vk.bytes32() != sidecar.vkey {
+        eprintln!("Error: the proof was made with the ELF {} and verification key {},", sidecar.elf, sidecar.vkey);
+        eprintln!("but this binary embeds the ELF {} with verification key {}", program.elf_sha256(), vk.bytes32());
+        std::process::exit(1);
+    }
+    if let Err(e) = client.verify(&proof, &vk) {
+        eprintln!("Error: {} does not verify: {}", args.proof.display(), e);
+        std::process::exit(1);
+    }
+    println!("Successfully verified proof!");
+
+    // The sidecar must claim exactly the public values the proof commits.
+    let claim = program.decode(sidecar.kind, proof.public_values.as_slice()).unwrap_or_else(|e| {
+        eprintln!("Error: the public values of the proof are not a {:?} claim: {:#}", sidecar.kind, e);
+        std::process::exit(1);
+    });
+    if claim.to_json() != sidecar.public_values {
+        eprintln!("Error: the public values of the proof differ from the claim in its sidecar");
+        std::process::exit(1);
+    }
+
+    // An aggregate proof only proves training if its segments were proven with the training
+    // program of this binary.
+    if let Claim::Aggregate(aggregate) = &claim {
+        let checked = training_program(aggregate).and_then(|training| {
+            let (_, training_vk) = setup(client, training);
+            check_training_vkey(aggregate, training, training_vk.hash_u32())
+        });
+        if let Err(e) = checked {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+        println!("The segments were proven with the training program of this binary.");
+    }
+
+    println!("program: {}", program.name());
+    println!("claim: {:?}", sidecar.kind);
+    claim.print();
+    println!("config: {}", sidecar.config);
+}
+
+/// Print the verification key of the program.
+fn vkey(client: &ProverClient, args: &VkeyArgs) {
+    let (_, vk) = client.setup(args.program.elf());
+    println!("{}", vk.bytes32());
+}
+
+/// Execute a prediction of `--model` on `--input` with the weights of a checkpoint, or prove it
+/// and write the proof to `proof`.
+fn infer(client: &ProverClient, args: &TrainArgs, proof: Option<&Path>) {
+    if args.program() != Program::Infer {
+        eprintln!("Error: Inference needs --input");
+        std::process::exit(1);
+    }
+    let checkpoint = args.load().checkpoint();
+    let input: Vec<u32> = args.input.iter().map(|&v| Fixed::from_f32(v).to_raw()).collect();
+    let program = Program::Infer;
+    let stdin = program.stdin(Inputs::Inference(&checkpoint, &input)).expect("inputs of the program");
+
+    let output = if let Some(path) = proof {
+        let (pk, vk) = setup(client, program);
+        let proof = client.prove(&pk, stdin).run().expect("failed to generate proof");
+        println!("Successfully generated proof!");
+
+        client.verify(&proof, &vk).expect("failed to verify proof");
+        println!("Successfully verified proof!");
+        save_proof(&proof, &vk, program, ClaimKind::Inference, args, path);
+        proof.public_values.to_vec()
+    } else {
+        println!("ELF sha256: {}", program.elf_sha256());
+        let (output, report) = client.execute(program.elf(), stdin).run().unwrap();
+        println!("Program executed successfully.");
+        println!("Number of cycles: {}", report.total_instruction_count());
+        output.to_vec()
+    };
+
+    check_inference(&output, &checkpoint, &input);
+    println!("Values are correct!");
+}
+
+/// Print the header and training state of a checkpoint, or the kind and public values of a proof.
+fn inspect(args: &InspectArgs) {
+    if let Ok(checkpoint) = checkpoint::load(&args.path) {
+        println!("checkpoint: {}", args.path.display());
+        println!("model: {:?}", checkpoint.model());
+        println!("architecture: {:?}", checkpoint.architecture());
+        println!("params: {}", checkpoint.params().len());
+        println!("digest: 0x{}", hex::encode(checkpoint.digest()));
+        print_training(&checkpoint.training());
+        return;
+    }
+
+    let proof = SP1ProofWithPublicValues::load(&args.path).unwrap_or_else(|e| {
+        eprintln!("Error: {} is neither a checkpoint nor a proof: {:#}", args.path.display(), e);
+        std::process::exit(1);
+    });
+    let kind = match proof.proof {
+        SP1Proof::Core(_) => "core",
+        SP1Proof::Compressed(_) => "compressed",
+        SP1Proof::Plonk(_) => "plonk",
+        SP1Proof::Groth16(_) => "groth16",
+    };
+    println!("proof: {}", args.path.display());
+    println!("kind: {}", kind);
+    println!("SP1 version: {}", proof.sp1_version);
+    println!("Public Values: 0x{}", hex::encode(proof.public_values.as_slice()));
+}
+
+/// Setup a program, and print the digest of its ELF and its verification key.
+fn setup(client: &ProverClient, program: Program) -> (SP1ProvingKey, SP1VerifyingKey) {
+    let (pk, vk) = client.setup(program.elf());
+    println!("ELF sha256: {}", program.elf_sha256());
+    println!("Verification Key: {}", vk.bytes32());
+    (pk, vk)
+}
+
+/// Write a proof to disk with its sidecar, so that it can be verified elsewhere with `verify`.
+fn save_proof(
+    proof: &SP1ProofWithPublicValues,
+    vk: &SP1VerifyingKey,
+    program: Program,
+    kind: ClaimKind,
+    args: &TrainArgs,
+    path: &Path,
+) {
+    proof.save(path).expect("failed to save proof");
+
+    let claim = program.decode(kind, proof.public_values.as_slice()).expect("public values of the claimed kind");
+    let sidecar = Sidecar {
+        program,
+        kind,
+        elf: program.elf_sha256().to_string(),
+        vkey: vk.bytes32(),
+        config: serde_json::to_value(args).expect("the flags serialize to JSON"),
+        public_values: claim.to_json(),
+    };
+    sidecar.save(path).expect("failed to save sidecar");
+    println!("Wrote the proof to {} and its claim to {}", path.display(), Sidecar::path(path).display());
+}
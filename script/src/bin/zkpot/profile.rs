@@ -0,0 +1,298 @@
+//! Profiling the cost of training in the guest. Each configuration of a sweep over hidden widths,
+//! epochs and dataset sizes trains the MLP on synthetic examples in the SP1 executor, and its
+//! cycles and syscalls are recorded. A cost model fitted to the sweep then predicts the cycles and
+//! shards of configurations too large to execute, such as a planned proving job.
+//!
+//! The report is written as CSV or JSON to stdout or `--out`, and progress and the fitted model
+//! to stderr.
+
+use clap::{Args, ValueEnum};
+use fibonacci_lib::{
+    ActivationKind, Commitment, Fixed, GuestMode, LayerSpec, Scalar, Sequential, TrainingState, EXTERNAL_DATASET,
+};
+use fibonacci_script::profile::{shards, CostModel, Workload, DEFAULT_SHARD_SIZE};
+use fibonacci_script::program::{Inputs, Program};
+use fibonacci_script::training::TrainingRun;
+use serde::Serialize;
+use sp1_sdk::ProverClient;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The arguments of the `profile` subcommand.
+#[derive(Args, Debug)]
+pub struct ProfileArgs {
+    /// The hidden widths of the MLP to sweep.
+    #[clap(long, value_delimiter = ',', default_value = "2,4,8", value_parser = clap::value_parser!(u32).range(1..))]
+    hidden: Vec<u32>,
+
+    /// The numbers of epochs to sweep.
+    #[clap(long, value_delimiter = ',', default_value = "1,2,4", value_parser = clap::value_parser!(u32).range(1..))]
+    epochs: Vec<u32>,
+
+    /// The numbers of training examples to sweep.
+    #[clap(long, value_delimiter = ',', default_value = "8,32", value_parser = clap::value_parser!(u32).range(1..))]
+    examples: Vec<u32>,
+
+    /// The number of features of the synthetic examples.
+    #[clap(long, default_value = "4", value_parser = clap::value_parser!(u32).range(1..))]
+    inputs: u32,
+
+    /// The number of classes of the synthetic examples.
+    #[clap(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..))]
+    classes: u32,
+
+    /// Predict the cost of a configuration given as `hidden:epochs:examples`. Can be repeated.
+    #[clap(long, value_parser = parse_planned)]
+    predict: Vec<(u32, u32, u32)>,
+
+    /// The number of cycles the prover proves at once.
+    #[clap(long, default_value_t = DEFAULT_SHARD_SIZE)]
+    shard_size: u64,
+
+    /// Exit with a non-zero status if a predicted configuration needs more shards than this.
+    #[clap(long)]
+    max_shards: Option<u64>,
+
+    /// The format of the report.
+    #[clap(long, value_enum, default_value = "csv")]
+    format: Format,
+
+    /// Write the report to this file instead of stdout.
+    #[clap(long)]
+    out: Option<PathBuf>,
+}
+
+/// Enum representing the formats of the report.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum Format {
+    Csv,
+    Json,
+}
+
+/// A configuration of the sweep, measured in the executor.
+#[derive(Serialize)]
+struct Measurement {
+    hidden: u32,
+    #[serde(flatten)]
+    workload: Workload,
+    cycles: u64,
+    syscalls: u64,
+    /// The number of calls of every syscall the guest made.
+    syscall_counts: BTreeMap<String, u64>,
+}
+
+/// A configuration whose cost is predicted by the cost model.
+#[derive(Serialize)]
+struct Prediction {
+    hidden: u32,
+    #[serde(flatten)]
+    workload: Workload,
+    cycles: u64,
+    shards: u64,
+}
+
+/// The report written in JSON.
+#[derive(Serialize)]
+struct Report<'a> {
+    inputs: u32,
+    classes: u32,
+    shard_size: u64,
+    runs: &'a [Measurement],
+    model: &'a CostModel,
+    predictions: &'a [Prediction],
+}
+
+/// Execute every configuration of the sweep, fit the cost model to their cycles, and report the
+/// runs and the predicted configurations. Exits with a non-zero status if a prediction needs more
+/// than `--max-shards` shards.
+pub fn profile(client: &ProverClient, args: &ProfileArgs) {
+    let program = Program::Mlp;
+    eprintln!("ELF sha256: {}", program.elf_sha256());
+
+    let mut runs = Vec::new();
+    for &hidden in &args.hidden {
+        for &epochs in &args.epochs {
+            for &examples in &args.examples {
+                let workload = workload(args, hidden, epochs, examples);
+                let run = training_run(args, hidden, epochs, examples);
+                let stdin = program.stdin(Inputs::Training(&run, Commitment::Clear)).expect("inputs of the program");
+                let (_, report) = client.execute(program.elf(), stdin).run().expect("failed to execute the program");
+
+                let syscall_counts = report
+                    .syscall_counts
+                    .iter()
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(code, count)| (format!("{:?}", code), *count))
+                    .collect();
+                let measurement = Measurement {
+                    hidden,
+                    workload,
+                    cycles: report.total_instruction_count(),
+                    syscalls: report.total_syscall_count(),
+                    syscall_counts,
+                };
+                eprintln!(
+                    "hidden {}, {} epochs, {} examples: {} cycles, {} syscalls",
+                    hidden, epochs, examples, measurement.cycles, measurement.syscalls
+                );
+                runs.push(measurement);
+            }
+        }
+    }
+
+    let samples: Vec<(Workload, u64)> = runs.iter().map(|run| (run.workload, run.cycles)).collect();
+    let model = CostModel::fit(&samples).unwrap_or_else(|e| {
+        eprintln!("Error: failed to fit the cost model: {}", e);
+        std::process::exit(1);
+    });
+    eprintln!(
+        "cycles = {:.0} + {:.2} * epochs * examples * params + {:.2} * examples * params (R^2 = {:.4})",
+        model.fixed, model.per_step, model.per_example, model.r_squared
+    );
+
+    let predictions: Vec<Prediction> = args
+        .predict
+        .iter()
+        .map(|&(hidden, epochs, examples)| {
+            let workload = workload(args, hidden, epochs, examples);
+            let cycles = model.predict(&workload).round() as u64;
+            Prediction { hidden, workload, cycles, shards: shards(cycles, args.shard_size) }
+        })
+        .collect();
+
+    let report = match args.format {
+        Format::Csv => csv(&runs, &model, &predictions, args.shard_size),
+        Format::Json => {
+            let report = Report {
+                inputs: args.inputs,
+                classes: args.classes,
+                shard_size: args.shard_size,
+                runs: &runs,
+                model: &model,
+                predictions: &predictions,
+            };
+            serde_json::to_string_pretty(&report).unwrap() + "\n"
+        }
+    };
+    match &args.out {
+        Some(path) => {
+            std::fs::write(path, report).expect("failed to write the report");
+            eprintln!("Wrote the report to {}", path.display());
+        }
+        None => print!("{}", report),
+    }
+
+    let mut over_budget = false;
+    for prediction in &predictions {
+        let fits = args.max_shards.map_or(true, |max| prediction.shards <= max);
+        over_budget |= !fits;
+        eprintln!(
+            "predicted hidden {}, {} epochs, {} examples: {} cycles in {} shards{}",
+            prediction.hidden,
+            prediction.workload.epochs,
+            prediction.workload.examples,
+            prediction.cycles,
+            prediction.shards,
+            if fits { "" } else { ", over budget" }
+        );
+    }
+    if over_budget {
+        std::process::exit(1);
+    }
+}
+
+/// The MLP of width `hidden` trained for `epochs` on `examples` synthetic examples.
+fn training_run(args: &ProfileArgs, hidden: u32, epochs: u32, examples: u32) -> TrainingRun {
+    let shape = [args.inputs, hidden, args.classes];
+    let params = Sequential::<Fixed>::seeded(&specs(shape), u64::from(hidden)).params();
+
+    TrainingRun {
+        mode: GuestMode::Mlp,
+        architecture: shape.to_vec(),
+        d: EXTERNAL_DATASET,
+        rows: synthetic_rows(args.inputs, args.classes, examples),
+        t: epochs,
+        r: Fixed::from_f32(0.01).to_raw(),
+        params: params.into_iter().map(Fixed::to_raw).collect(),
+        training: TrainingState::sgd(),
+        held_out: None,
+    }
+}
+
+/// The size of the run returned by [`training_run`], exiting with an error if the MLP has more
+/// parameters than a checkpoint can hold.
+fn workload(args: &ProfileArgs, hidden: u32, epochs: u32, examples: u32) -> Workload {
+    let params = LayerSpec::total_params(&specs([args.inputs, hidden, args.classes])).unwrap_or_else(|| {
+        eprintln!("Error: an MLP of hidden width {} has too many parameters", hidden);
+        std::process::exit(1);
+    });
+    Workload { params: params as u64, epochs: u64::from(epochs), examples: u64::from(examples) }
+}
+
+fn specs([inputs, hidden, classes]: [u32; 3]) -> [LayerSpec; 2] {
+    [LayerSpec::new(inputs, hidden, ActivationKind::Relu), LayerSpec::new(hidden, classes, ActivationKind::Identity)]
+}
+
+/// Examples with features in `[-1, 1)` and a one-hot label, the same for every configuration of
+/// the sweep.
+fn synthetic_rows(inputs: u32, classes: u32, examples: u32) -> Vec<Vec<u32>> {
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    (0..examples)
+        .map(|_| {
+            let mut row: Vec<u32> = (0..inputs)
+                .map(|_| Fixed::from_f32((next() >> 40) as f32 / (1u64 << 23) as f32 - 1.0).to_raw())
+                .collect();
+            let label = next() % u64::from(classes);
+            row.extend((0..u64::from(classes)).map(|c| if c == label { Fixed::ONE } else { Fixed::ZERO }.to_raw()));
+            row
+        })
+        .collect()
+}
+
+/// The measured and the predicted configurations as one CSV table. Predicted configurations have
+/// no measured cycles or syscalls.
+fn csv(runs: &[Measurement], model: &CostModel, predictions: &[Prediction], shard_size: u64) -> String {
+    let mut csv = String::from("hidden,params,epochs,examples,cycles,syscalls,predicted_cycles,predicted_shards\n");
+    for run in runs {
+        let predicted = model.predict(&run.workload).round() as u64;
+        let Workload { params, epochs, examples } = run.workload;
+        csv += &format!(
+            "{},{},{},{},{},{},{},{}\n",
+            run.hidden,
+            params,
+            epochs,
+            examples,
+            run.cycles,
+            run.syscalls,
+            predicted,
+            shards(predicted, shard_size)
+        );
+    }
+    for prediction in predictions {
+        let Workload { params, epochs, examples } = prediction.workload;
+        csv += &format!(
+            "{},{},{},{},,,{},{}\n",
+            prediction.hidden, params, epochs, examples, prediction.cycles, prediction.shards
+        );
+    }
+    csv
+}
+
+/// Parse a planned configuration given as `hidden:epochs:examples`.
+fn parse_planned(s: &str) -> Result<(u32, u32, u32), String> {
+    let parts: Vec<u32> = s
+        .split(':')
+        .map(|part| part.trim().parse::<u32>().map_err(|e| format!("invalid number {:?}: {}", part, e)))
+        .collect::<Result<_, _>>()?;
+    match parts[..] {
+        [hidden, epochs, examples] if hidden > 0 && epochs > 0 && examples > 0 => Ok((hidden, epochs, examples)),
+        _ => Err(format!("expected hidden:epochs:examples, all positive, got {:?}", s)),
+    }
+}
@@ -0,0 +1,116 @@
+//! Training in segments of `k` epochs, with the segment proofs aggregated into a single proof.
+
+use alloy_sol_types::SolType;
+use fibonacci_lib::{AggregatePublicValuesStruct, Commitment, DigestPublicValuesStruct};
+use fibonacci_script::claim::print_evaluation;
+use fibonacci_script::program::{Inputs, Program};
+use fibonacci_script::training::TrainingRun;
+use sp1_sdk::{HashableKey, ProverClient, SP1ProofWithPublicValues, SP1ProvingKey, SP1Stdin, SP1VerifyingKey};
+use std::path::Path;
+
+use crate::check::check_digests;
+use crate::setup;
+
+/// Train in segments of `k` epochs, each executed or proven on its own, and check that they chain
+/// into the full run. When proving, the segment proofs are aggregated into a single proof, which
+/// is returned with the verification key of the aggregation program.
+pub fn train_in_segments(
+    client: &ProverClient,
+    run: &TrainingRun,
+    k: u32,
+    prove: bool,
+    segments_dir: Option<&Path>,
+) -> Option<(SP1ProofWithPublicValues, SP1VerifyingKey)> {
+    // Every segment is proven by the program that trains the model.
+    let program = Program::for_mode(run.mode);
+    let (pk, vk) = setup(client, program);
+
+    let mut segment = run.clone();
+    let mut public_values = Vec::new();
+    let mut proofs = Vec::new();
+    let mut epochs = 0;
+    while epochs < run.t {
+        segment.t = k.min(run.t - epochs);
+        let stdin = program.stdin(Inputs::Training(&segment, Commitment::Digest)).expect("inputs of the program");
+        let index = public_values.len();
+
+        let output = if prove {
+            let proof = prove_segment(client, &pk, &vk, stdin, segments_dir, index);
+            let output = proof.public_values.to_vec();
+            proofs.push(proof);
+            output
+        } else {
+            let (output, report) = client.execute(program.elf(), stdin).run().unwrap();
+            println!("Segment {}: {} cycles", index, report.total_instruction_count());
+            output.to_vec()
+        };
+
+        // Each segment must resume from the weights and optimizer state the previous one ended with.
+        let trained = segment.train_native();
+        check_digests(&output, &segment, &trained);
+        public_values.push(output);
+        epochs += segment.t;
+        segment = segment.resume(&trained);
+    }
+
+    let segments: Vec<DigestPublicValuesStruct> = public_values
+        .iter()
+        .map(|bytes| DigestPublicValuesStruct::abi_decode(bytes, true).unwrap())
+        .collect();
+    let expected = AggregatePublicValuesStruct::chain(vk.hash_u32(), &segments).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+    println!("Checked a chain of {} segments over {} epochs.", segments.len(), expected.t);
+
+    let aggregated = prove.then(|| {
+        // Setup the inputs of the aggregation program.
+        let stdin = Program::Aggregate.stdin(Inputs::Aggregation(&vk, proofs)).expect("compressed segment proofs");
+
+        let (aggregation_pk, aggregation_vk) = setup(client, Program::Aggregate);
+        let proof = client.prove(&aggregation_pk, stdin).run().expect("failed to generate aggregation proof");
+        println!("Successfully generated aggregation proof!");
+
+        client.verify(&proof, &aggregation_vk).expect("failed to verify aggregation proof");
+        println!("Successfully verified aggregation proof!");
+
+        assert_eq!(proof.public_values.as_slice(), AggregatePublicValuesStruct::abi_encode(&expected));
+        (proof, aggregation_vk)
+    });
+
+    println!("weights_digest: {}", expected.weights_digest);
+    println!("updated_weights_digest: {}", expected.updated_weights_digest);
+    print_evaluation(&expected.evaluation);
+
+    aggregated
+}
+
+/// Prove one segment with a compressed proof, which the aggregation program can verify, or load
+/// it from `dir` if an earlier run already proved it.
+fn prove_segment(
+    client: &ProverClient,
+    pk: &SP1ProvingKey,
+    vk: &SP1VerifyingKey,
+    stdin: SP1Stdin,
+    dir: Option<&Path>,
+    index: usize,
+) -> SP1ProofWithPublicValues {
+    let path = dir.map(|dir| dir.join(format!("segment-{}.bin", index)));
+
+    if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+        let proof = SP1ProofWithPublicValues::load(path).expect("failed to load segment proof");
+        client.verify(&proof, vk).expect("failed to verify segment proof");
+        println!("Loaded segment {} from {}", index, path.display());
+        return proof;
+    }
+
+    let proof = client.prove(pk, stdin).compressed().run().expect("failed to generate segment proof");
+    println!("Proved segment {}", index);
+
+    if let (Some(dir), Some(path)) = (dir, &path) {
+        std::fs::create_dir_all(dir).expect("failed to create segments directory");
+        proof.save(path).expect("failed to save segment proof");
+    }
+
+    proof
+}
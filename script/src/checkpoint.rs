@@ -0,0 +1,26 @@
+//! Saving and loading checkpoints.
+//!
+//! Checkpoints are stored in their compact binary encoding, or as JSON when the file name ends in
+//! `.json`. Either way the digest is checked when the file is loaded.
+
+use anyhow::{Context, Result};
+use fibonacci_lib::Checkpoint;
+use std::path::Path;
+
+pub fn save(path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let bytes = if is_json(path) { serde_json::to_vec_pretty(checkpoint)? } else { checkpoint.to_bytes() };
+    std::fs::write(path, bytes).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn load(path: &Path) -> Result<Checkpoint> {
+    let bytes = std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if is_json(path) {
+        serde_json::from_slice(&bytes).with_context(|| format!("invalid checkpoint in {}", path.display()))
+    } else {
+        Checkpoint::from_bytes(&bytes).with_context(|| format!("invalid checkpoint in {}", path.display()))
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
@@ -0,0 +1,386 @@
+//! What a proof claims, decoded from its public values, and the JSON sidecar saved next to it.
+//!
+//! A proof file only holds the ABI-encoded public values, and which struct they encode depends on
+//! the guest mode and the commitment of the run. The [`Sidecar`] written next to a proof records
+//! the [`Program`] that was proven and that [`ClaimKind`], the digest of its ELF and its
+//! verification key hash, the flags of the run as a config table and the decoded public values, so
+//! a verifier on another machine can check the proof against the ELF and read what it claims
+//! without the inputs of the run.
+
+use alloy_sol_types::SolType;
+use anyhow::{bail, Context, Result};
+use fibonacci_lib::{
+    AggregatePublicValuesStruct, Commitment, DigestPublicValuesStruct, EvaluationStruct, Fixed, GuestMode,
+    InferencePublicValuesStruct, LayerSpec, LossKind, MlpPublicValuesStruct, OptimizerStruct, PublicValuesStruct,
+    Scalar, SequentialPublicValuesStruct,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::program::Program;
+
+/// Which struct the public values of a proof encode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClaimKind {
+    Perceptron,
+    Sequential,
+    Mlp,
+    Digest,
+    Inference,
+    Aggregate,
+}
+
+impl ClaimKind {
+    /// The public values of a training run of `mode` that commits with `commitment`.
+    pub fn training(mode: GuestMode, commitment: Commitment) -> Self {
+        match (commitment, mode) {
+            (Commitment::Clear, GuestMode::Perceptron) => ClaimKind::Perceptron,
+            (Commitment::Clear, GuestMode::Sequential) => ClaimKind::Sequential,
+            (Commitment::Clear, GuestMode::Mlp) => ClaimKind::Mlp,
+            (Commitment::Digest, _) => ClaimKind::Digest,
+            (_, GuestMode::Infer) => ClaimKind::Inference,
+        }
+    }
+}
+
+/// The decoded public values of a proof.
+#[derive(Clone)]
+pub enum Claim {
+    Perceptron(PublicValuesStruct),
+    Sequential(SequentialPublicValuesStruct),
+    Mlp(MlpPublicValuesStruct),
+    Digest(DigestPublicValuesStruct),
+    Inference(InferencePublicValuesStruct),
+    Aggregate(AggregatePublicValuesStruct),
+}
+
+impl Claim {
+    /// Decode public values as the struct of `kind`.
+    pub fn decode(kind: ClaimKind, bytes: &[u8]) -> Result<Self> {
+        let claim = match kind {
+            ClaimKind::Perceptron => Claim::Perceptron(PublicValuesStruct::abi_decode(bytes, true)?),
+            ClaimKind::Sequential => Claim::Sequential(SequentialPublicValuesStruct::abi_decode(bytes, true)?),
+            ClaimKind::Mlp => Claim::Mlp(MlpPublicValuesStruct::abi_decode(bytes, true)?),
+            ClaimKind::Digest => Claim::Digest(DigestPublicValuesStruct::abi_decode(bytes, true)?),
+            ClaimKind::Inference => Claim::Inference(InferencePublicValuesStruct::abi_decode(bytes, true)?),
+            ClaimKind::Aggregate => Claim::Aggregate(AggregatePublicValuesStruct::abi_decode(bytes, true)?),
+        };
+
+        Ok(claim)
+    }
+
+    /// The public values as JSON, with numbers as raw `u32` words and digests in hex.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Claim::Perceptron(v) => json!({
+                "d": v.d,
+                "dataset_root": v.dataset_root.to_string(),
+                "t": v.t,
+                "r": v.r,
+                "w_l": v.w_l,
+                "w_r": v.w_r,
+                "b": v.b,
+                "updated_w_l": v.updated_w_l,
+                "updated_w_r": v.updated_w_r,
+                "updated_b": v.updated_b,
+                "optimizer": optimizer_json(&v.optimizer),
+                "evaluation": evaluation_json(&v.evaluation),
+            }),
+            Claim::Sequential(v) => json!({
+                "layers": v.layers,
+                "d": v.d,
+                "dataset_root": v.dataset_root.to_string(),
+                "t": v.t,
+                "r": v.r,
+                "params": v.params,
+                "updated_params": v.updated_params,
+                "optimizer": optimizer_json(&v.optimizer),
+                "evaluation": evaluation_json(&v.evaluation),
+            }),
+            Claim::Mlp(v) => json!({
+                "shape": v.shape,
+                "d": v.d,
+                "dataset_root": v.dataset_root.to_string(),
+                "t": v.t,
+                "r": v.r,
+                "params": v.params,
+                "updated_params": v.updated_params,
+                "optimizer": optimizer_json(&v.optimizer),
+                "evaluation": evaluation_json(&v.evaluation),
+            }),
+            Claim::Digest(v) => json!({
+                "mode": v.mode,
+                "t": v.t,
+                "r": v.r,
+                "dataset_root": v.dataset_root.to_string(),
+                "weights_digest": v.weights_digest.to_string(),
+                "updated_weights_digest": v.updated_weights_digest.to_string(),
+                "evaluation": evaluation_json(&v.evaluation),
+            }),
+            Claim::Inference(v) => json!({
+                "model": v.model,
+                "weights_digest": v.weights_digest.to_string(),
+                "input_digest": v.input_digest.to_string(),
+                "output": v.output,
+            }),
+            Claim::Aggregate(v) => json!({
+                "vkey": v.vkey.to_string(),
+                "mode": v.mode,
+                "t": v.t,
+                "r": v.r,
+                "dataset_root": v.dataset_root.to_string(),
+                "weights_digest": v.weights_digest.to_string(),
+                "updated_weights_digest": v.updated_weights_digest.to_string(),
+                "evaluation": evaluation_json(&v.evaluation),
+            }),
+        }
+    }
+
+    /// Print the claim, with raw values decoded into the lib's numeric type.
+    pub fn print(&self) {
+        match self {
+            Claim::Perceptron(v) => print_perceptron(v),
+            Claim::Sequential(v) => print_sequential(v),
+            Claim::Mlp(v) => print_mlp(v),
+            Claim::Digest(v) => {
+                println!("mode: {}", mode_name(v.mode));
+                println!("t: {}", v.t);
+                println!("r: {}", Fixed::from_raw(v.r).to_f32());
+                println!("dataset_root: {}", v.dataset_root);
+                println!("weights_digest: {}", v.weights_digest);
+                println!("updated_weights_digest: {}", v.updated_weights_digest);
+                print_evaluation(&v.evaluation);
+            }
+            Claim::Inference(v) => {
+                println!("model: {}", mode_name(v.model));
+                println!("weights_digest: {}", v.weights_digest);
+                println!("input_digest: {}", v.input_digest);
+                println!("output: {:?}", decode_params(&v.output));
+            }
+            Claim::Aggregate(v) => {
+                println!("training program vkey: {}", v.vkey);
+                println!("mode: {}", mode_name(v.mode));
+                println!("t: {}", v.t);
+                println!("r: {}", Fixed::from_raw(v.r).to_f32());
+                println!("dataset_root: {}", v.dataset_root);
+                println!("weights_digest: {}", v.weights_digest);
+                println!("updated_weights_digest: {}", v.updated_weights_digest);
+                print_evaluation(&v.evaluation);
+            }
+        }
+    }
+}
+
+/// The JSON file written next to a proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sidecar {
+    /// The program that was proven.
+    pub program: Program,
+    /// Which struct the public values encode.
+    pub kind: ClaimKind,
+    /// The SHA-256 digest of the ELF that was proven.
+    pub elf: String,
+    /// The verification key hash of the program that was proven.
+    pub vkey: String,
+    /// The flags of the run, as a table that can be passed back with `--config`.
+    pub config: Value,
+    /// The public values, as [`Claim::to_json`] decodes them.
+    pub public_values: Value,
+}
+
+impl Sidecar {
+    /// The path of the sidecar of the proof at `proof`, which is `proof` with `.json` appended.
+    pub fn path(proof: &Path) -> PathBuf {
+        let mut path = proof.as_os_str().to_owned();
+        path.push(".json");
+        PathBuf::from(path)
+    }
+
+    /// Write the sidecar next to the proof at `proof`.
+    pub fn save(&self, proof: &Path) -> Result<()> {
+        let path = Self::path(proof);
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, json + "\n").with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Read the sidecar of the proof at `proof`.
+    pub fn load(proof: &Path) -> Result<Self> {
+        let path = Self::path(proof);
+        let json = std::fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("invalid sidecar in {}", path.display()))
+    }
+}
+
+/// Print the public values of a perceptron run.
+pub fn print_perceptron(v: &PublicValuesStruct) {
+    println!("d: {}", v.d);
+    println!("dataset_root: {}", v.dataset_root);
+    println!("t: {}", v.t);
+    println!("r: {}", Fixed::from_raw(v.r).to_f32());
+    println!("w_l: {}", Fixed::from_raw(v.w_l).to_f32());
+    println!("w_r: {}", Fixed::from_raw(v.w_r).to_f32());
+    println!("b: {}", Fixed::from_raw(v.b).to_f32());
+    println!("updated_w_l: {}", Fixed::from_raw(v.updated_w_l).to_f32());
+    println!("updated_w_r: {}", Fixed::from_raw(v.updated_w_r).to_f32());
+    println!("updated_b: {}", Fixed::from_raw(v.updated_b).to_f32());
+    print_optimizer(&v.optimizer);
+    print_evaluation(&v.evaluation);
+}
+
+/// Print the public values of a dense network run.
+pub fn print_sequential(v: &SequentialPublicValuesStruct) {
+    println!("layers: {:?}", LayerSpec::decode_all(&v.layers));
+    println!("d: {}", v.d);
+    println!("dataset_root: {}", v.dataset_root);
+    println!("t: {}", v.t);
+    println!("r: {}", Fixed::from_raw(v.r).to_f32());
+    println!("updated_params: {:?}", decode_params(&v.updated_params));
+    print_optimizer(&v.optimizer);
+    print_evaluation(&v.evaluation);
+}
+
+/// Print the public values of an MLP run.
+pub fn print_mlp(v: &MlpPublicValuesStruct) {
+    println!("shape: {:?}", v.shape);
+    println!("d: {}", v.d);
+    println!("dataset_root: {}", v.dataset_root);
+    println!("t: {}", v.t);
+    println!("r: {}", Fixed::from_raw(v.r).to_f32());
+    println!("updated_params: {:?}", decode_params(&v.updated_params));
+    print_optimizer(&v.optimizer);
+    print_evaluation(&v.evaluation);
+}
+
+/// Print the committed optimizer of a run.
+pub fn print_optimizer(optimizer: &OptimizerStruct) {
+    println!("optimizer: {} (batch size {})", optimizer.kind, optimizer.batch_size);
+    println!("epoch: {}", optimizer.epoch);
+    println!("optimizer state digest: {}", optimizer.state_digest);
+    println!("updated optimizer state digest: {}", optimizer.updated_state_digest);
+}
+
+/// Print the committed loss and accuracy of the trained model.
+pub fn print_evaluation(evaluation: &EvaluationStruct) {
+    let accuracy = evaluation.correct as f32 / evaluation.total.max(1) as f32;
+
+    println!("evaluation root: {}", evaluation.dataset_root);
+    match LossKind::from_u32(evaluation.loss_function) {
+        Some(loss) => println!("loss function: {:?}", loss),
+        None => println!("loss function: unknown ({})", evaluation.loss_function),
+    }
+    println!("loss: {}", Fixed::from_raw(evaluation.loss).to_f32());
+    println!("accuracy: {}/{} ({:.1}%)", evaluation.correct, evaluation.total, 100.0 * accuracy);
+}
+
+/// The training program whose segments an aggregate claim chains, from the guest mode it commits.
+pub fn training_program(aggregate: &AggregatePublicValuesStruct) -> Result<Program> {
+    match GuestMode::from_u32(aggregate.mode) {
+        Some(GuestMode::Infer) | None => {
+            bail!("the aggregate claims {} segments, not training", mode_name(aggregate.mode))
+        }
+        Some(mode) => Ok(Program::for_mode(mode)),
+    }
+}
+
+/// Check that the segments of an aggregate claim were proven with `program`, whose verifying key
+/// hash is `vkey` as eight words. The aggregation program verifies the segments with the key it is
+/// given and commits it, so without this check the claim could chain proofs of any other program.
+pub fn check_training_vkey(aggregate: &AggregatePublicValuesStruct, program: Program, vkey: [u32; 8]) -> Result<()> {
+    let expected = AggregatePublicValuesStruct::vkey_bytes(vkey);
+    if aggregate.vkey != expected {
+        bail!(
+            "the segments were proven with verification key hash {}, but the {} program has 0x{}",
+            aggregate.vkey,
+            program.name(),
+            hex::encode(expected)
+        );
+    }
+
+    Ok(())
+}
+
+/// Decode raw parameters into floats for display.
+pub fn decode_params(params: &[u32]) -> Vec<f32> {
+    params.iter().map(|&p| Fixed::from_raw(p).to_f32()).collect()
+}
+
+/// The name of a committed guest mode.
+fn mode_name(mode: u32) -> String {
+    match GuestMode::from_u32(mode) {
+        Some(mode) => format!("{:?}", mode),
+        None => format!("unknown ({})", mode),
+    }
+}
+
+fn optimizer_json(optimizer: &OptimizerStruct) -> Value {
+    json!({
+        "kind": optimizer.kind,
+        "batch_size": optimizer.batch_size,
+        "schedule": optimizer.schedule,
+        "schedule_epochs": optimizer.schedule_epochs,
+        "schedule_factor": optimizer.schedule_factor,
+        "beta1": optimizer.beta1,
+        "beta2": optimizer.beta2,
+        "epsilon": optimizer.epsilon,
+        "epoch": optimizer.epoch,
+        "state_digest": optimizer.state_digest.to_string(),
+        "updated_state_digest": optimizer.updated_state_digest.to_string(),
+    })
+}
+
+fn evaluation_json(evaluation: &EvaluationStruct) -> Value {
+    json!({
+        "dataset_root": evaluation.dataset_root.to_string(),
+        "loss_function": evaluation.loss_function,
+        "loss": evaluation.loss,
+        "correct": evaluation.correct,
+        "total": evaluation.total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fibonacci_lib::{update_checkpoint, Checkpoint, DatasetId, MerkleTree, Metrics};
+
+    // An aggregate of a single perceptron segment on AND, proven with `vkey`.
+    fn aggregate(vkey: [u32; 8]) -> AggregatePublicValuesStruct {
+        let (rows, r) = (DatasetId::And.rows(), Fixed::from_f32(0.1).to_raw());
+        let checkpoint = Checkpoint::perceptron(0, 0, 0);
+        let updated = update_checkpoint(&checkpoint, &rows, 2, r);
+        let root = MerkleTree::new(&rows).root();
+        let metrics = Metrics { loss: Fixed::ZERO, correct: 4, total: 4 };
+        let evaluation = EvaluationStruct::new(root, LossKind::Mse, metrics);
+        let segment = DigestPublicValuesStruct::new(root, 2, r, &checkpoint, &updated, evaluation);
+
+        AggregatePublicValuesStruct::chain(vkey, &[segment]).unwrap()
+    }
+
+    #[test]
+    fn accepts_the_training_vkey() {
+        let vkey = [1, 2, 3, 4, 5, 6, 7, 8];
+        let aggregate = aggregate(vkey);
+        assert_eq!(training_program(&aggregate).unwrap(), Program::Perceptron);
+        assert!(check_training_vkey(&aggregate, Program::Perceptron, vkey).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_mismatched_vkey() {
+        let aggregate = aggregate([1, 2, 3, 4, 5, 6, 7, 8]);
+        let error = check_training_vkey(&aggregate, Program::Perceptron, [1, 2, 3, 4, 5, 6, 7, 9]).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("0x0000000100000002000000030000000400000005000000060000000700000008"), "{}", message);
+        assert!(message.contains("0x0000000100000002000000030000000400000005000000060000000700000009"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_aggregates_of_inference() {
+        let mut aggregate = aggregate([0; 8]);
+        aggregate.mode = GuestMode::Infer.to_u32();
+        assert!(training_program(&aggregate).is_err());
+        aggregate.mode = 42;
+        assert!(training_program(&aggregate).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+//! Training configs, so that a run can be reproduced from a file.
+//!
+//! A config is a JSON or TOML table whose keys are the long flags of the command line, for
+//! example `{ "model": "mlp", "t": 20, "r": 0.05, "layers": ["2:4:relu", "4:1:identity"] }`.
+//! [`expand_args`] turns the file passed with `--config` into flags placed after the subcommand and
+//! before the flags given on the command line, so every value is parsed and validated the same
+//! way, and flags given explicitly override the config.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::path::Path;
+
+/// Insert the flags of the config file passed with `--config` before the other flags of `args`,
+/// after the program name and the subcommand, if any. Global flags given before the subcommand,
+/// such as `--mock`, stay in front of it. They take no values, so the subcommand is the first
+/// argument that is not a flag.
+pub fn expand_args(args: Vec<String>) -> Result<Vec<String>> {
+    let mut rest = args.into_iter().peekable();
+    let mut expanded: Vec<String> = rest.next().into_iter().collect();
+    while let Some(flag) = rest.next_if(|arg| arg.starts_with('-') && !arg.starts_with("--config")) {
+        expanded.push(flag);
+    }
+    expanded.extend(rest.next_if(|arg| !arg.starts_with('-')));
+    let mut explicit = Vec::new();
+
+    while let Some(arg) = rest.next() {
+        let path = match arg.strip_prefix("--config") {
+            Some("") => rest.next().context("--config needs a path")?,
+            Some(path) if path.starts_with('=') => path[1..].to_string(),
+            _ => {
+                explicit.push(arg);
+                continue;
+            }
+        };
+        expanded.extend(load(Path::new(&path))?);
+        explicit.push(format!("--config={}", path));
+    }
+
+    expanded.extend(explicit);
+    Ok(expanded)
+}
+
+/// Read a config file as a list of flags. TOML is expected for `.toml` files, JSON otherwise.
+pub fn load(path: &Path) -> Result<Vec<String>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let config: Value = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str(&contents).with_context(|| format!("invalid TOML in {}", path.display()))?
+    } else {
+        serde_json::from_str(&contents).with_context(|| format!("invalid JSON in {}", path.display()))?
+    };
+
+    let Value::Object(table) = config else {
+        bail!("{} must hold a table of flags", path.display());
+    };
+
+    let mut flags = Vec::new();
+    for (key, value) in table {
+        let flag = format!("--{}", key.replace('_', "-"));
+        match value {
+            Value::Bool(true) => flags.push(flag),
+            Value::Bool(false) | Value::Null => {}
+            Value::Array(values) => {
+                let values: Result<Vec<String>> = values.into_iter().map(|v| scalar(&key, v)).collect();
+                flags.push(format!("{}={}", flag, values?.join(",")));
+            }
+            value => flags.push(format!("{}={}", flag, scalar(&key, value)?)),
+        }
+    }
+
+    Ok(flags)
+}
+
+fn scalar(key: &str, value: Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        _ => bail!("unsupported value for `{}` in config", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn places_the_config_after_the_subcommand_and_global_flags() {
+        let path = std::env::temp_dir().join(format!("zkpot-config-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{ "model": "mlp", "t": 20 }"#).unwrap();
+        let config = format!("--config={}", path.display());
+
+        let expanded = expand_args(args(&["zkpot", "--mock", "prove", &config, "--t", "5"])).unwrap();
+        assert_eq!(expanded, args(&["zkpot", "--mock", "prove", "--model=mlp", "--t=20", &config, "--t", "5"]));
+
+        let expanded = expand_args(args(&["zkpot", "prove", "--mock", &config])).unwrap();
+        assert_eq!(expanded, args(&["zkpot", "prove", "--model=mlp", "--t=20", "--mock", &config]));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
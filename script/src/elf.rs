@@ -0,0 +1,42 @@
+//! Checks that the embedded guest ELFs are the ones this binary was built with.
+//!
+//! The build script builds the guests into the `elf` directory of the workspace and exports the
+//! path and SHA-256 digest of each ELF, and [`Program::elf`] embeds the ELF at that path. A later build
+//! of the guests, for example with other features, overwrites the ELF on disk but not the copy
+//! embedded in binaries built before it, which would then execute and prove an outdated guest.
+
+use anyhow::{ensure, Result};
+use sha2::{Digest, Sha256};
+
+use crate::program::Program;
+
+/// The SHA-256 digest of an ELF in hex.
+pub fn sha256(elf: &[u8]) -> String {
+    hex::encode(Sha256::digest(elf))
+}
+
+/// Check that an embedded ELF has the digest the build script exported, and that the guest at
+/// `path` has not been rebuilt since.
+pub fn check(elf: &[u8], path: &str, digest: &str) -> Result<()> {
+    let embedded = sha256(elf);
+    ensure!(embedded == digest, "the ELF embedded from {} has digest {}, not {}", path, embedded, digest);
+
+    // The ELF is missing when the binary runs on another machine than it was built on.
+    if let Ok(built) = std::fs::read(path) {
+        let built = sha256(&built);
+        ensure!(
+            built == digest,
+            "{} was rebuilt since this binary was built (digest {}, embedded {}), rebuild the script",
+            path,
+            built,
+            digest
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the embedded ELF of every program.
+pub fn check_embedded() -> Result<()> {
+    Program::ALL.into_iter().try_for_each(|program| check(program.elf(), program.elf_path(), program.elf_sha256()))
+}
@@ -0,0 +1,10 @@
+//! Host-side helpers shared by the script binaries.
+
+pub mod checkpoint;
+pub mod claim;
+pub mod config;
+pub mod elf;
+pub mod mnist;
+pub mod profile;
+pub mod program;
+pub mod training;
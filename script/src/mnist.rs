@@ -0,0 +1,304 @@
+//! A loader for the MNIST parquet files fetched by `download_mnist.sh`.
+//!
+//! Each parquet row holds an `image` struct with PNG-encoded `bytes` and an integer `label`.
+//! [`load`] decodes them into [`MnistImage`]s, which can be subsampled and downscaled to keep a
+//! training run small enough for the zkVM. [`to_rows`] then encodes them as raw examples in the
+//! lib's numeric type, ready to be written to `SP1Stdin` as an external dataset.
+
+use anyhow::{anyhow, bail, Context, Result};
+use fibonacci_lib::{Fixed, Scalar};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::{Field, Row};
+use std::fs::File;
+use std::path::Path;
+
+/// The number of digit classes.
+pub const CLASSES: usize = 10;
+
+/// A grayscale image with its digit label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MnistImage {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major intensities, 0 is black and 255 is white.
+    pub pixels: Vec<u8>,
+    pub label: u8,
+}
+
+/// Load every image of a parquet file.
+pub fn load(path: &Path) -> Result<Vec<MnistImage>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let reader = SerializedFileReader::new(file)?;
+
+    reader.get_row_iter(None)?.map(|row| parse_row(&row?)).collect()
+}
+
+fn parse_row(row: &Row) -> Result<MnistImage> {
+    let mut png = None;
+    let mut label = None;
+
+    for (name, field) in row.get_column_iter() {
+        match (name.as_str(), field) {
+            ("image", Field::Group(image)) => {
+                for (name, field) in image.get_column_iter() {
+                    if let ("bytes", Field::Bytes(bytes)) = (name.as_str(), field) {
+                        png = Some(bytes.data());
+                    }
+                }
+            }
+            ("label", Field::Long(l)) => label = Some(*l),
+            ("label", Field::Int(l)) => label = Some(i64::from(*l)),
+            _ => {}
+        }
+    }
+
+    let png = png.ok_or_else(|| anyhow!("row has no image bytes"))?;
+    let label = label.ok_or_else(|| anyhow!("row has no label"))?;
+    if !(0..CLASSES as i64).contains(&label) {
+        bail!("label {} is not a digit", label);
+    }
+
+    let (width, height, pixels) = decode_png(png)?;
+    Ok(MnistImage { width, height, pixels, label: label as u8 })
+}
+
+fn decode_png(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>)> {
+    let mut reader = png::Decoder::new(bytes).read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+
+    if info.color_type != png::ColorType::Grayscale || info.bit_depth != png::BitDepth::Eight {
+        bail!("expected an 8-bit grayscale image, got {:?} {:?}", info.color_type, info.bit_depth);
+    }
+    buf.truncate(info.buffer_size());
+
+    Ok((info.width as usize, info.height as usize, buf))
+}
+
+/// Pick `n` images pseudo-randomly. The same seed always selects the same images in the same order.
+pub fn subsample(mut images: Vec<MnistImage>, n: usize, seed: u64) -> Vec<MnistImage> {
+    let n = n.min(images.len());
+    let mut state = seed | 1;
+
+    // A partial Fisher-Yates shuffle driven by xorshift.
+    for i in 0..n {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let j = i + (state % (images.len() - i) as u64) as usize;
+        images.swap(i, j);
+    }
+
+    images.truncate(n);
+    images
+}
+
+impl MnistImage {
+    /// Shrink the image by `factor` in each direction, averaging each block of pixels.
+    ///
+    /// Blocks at the right and bottom edges may be smaller when the size is not a multiple of
+    /// `factor`.
+    pub fn downscale(&self, factor: usize) -> MnistImage {
+        assert!(factor > 0, "downscale factor must be positive");
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for by in 0..height {
+            for bx in 0..width {
+                let ys = by * factor..((by + 1) * factor).min(self.height);
+                let xs = bx * factor..((bx + 1) * factor).min(self.width);
+                let count = ys.len() * xs.len();
+                let sum: usize = ys
+                    .flat_map(|y| xs.clone().map(move |x| self.pixels[y * self.width + x] as usize))
+                    .sum();
+                pixels.push(((sum + count / 2) / count) as u8);
+            }
+        }
+
+        MnistImage { width, height, pixels, label: self.label }
+    }
+}
+
+/// Encode images as raw examples: the pixels scaled to `[0, 1]`, followed by the one-hot label.
+pub fn to_rows(images: &[MnistImage]) -> Vec<Vec<u32>> {
+    images
+        .iter()
+        .map(|image| {
+            let pixels = image.pixels.iter().map(|&p| Fixed::from_f32(p as f32 / 255.0));
+            let label = (0..CLASSES).map(|c| if c == image.label as usize { Fixed::ONE } else { Fixed::ZERO });
+            pixels.chain(label).map(Fixed::to_raw).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    // The schema of the MNIST files on the Hugging Face hub, without the unused image path.
+    const SCHEMA: &str = "
+        message schema {
+            REQUIRED GROUP image {
+                OPTIONAL BYTE_ARRAY bytes;
+            }
+            REQUIRED INT64 label;
+        }
+    ";
+
+    fn encode_png(width: u32, height: u32, color: png::ColorType, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(pixels).unwrap();
+
+        bytes
+    }
+
+    // Write a parquet file of PNG images and labels into a fresh temporary directory.
+    fn write_fixture(rows: &[(Vec<u8>, i64)]) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mnist.parquet");
+        let schema = Arc::new(parse_message_type(SCHEMA).unwrap());
+        let properties = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(File::create(&path).unwrap(), schema, properties).unwrap();
+
+        let mut row_group = writer.next_row_group().unwrap();
+        let images: Vec<ByteArray> = rows.iter().map(|(png, _)| ByteArray::from(png.clone())).collect();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column.typed::<ByteArrayType>().write_batch(&images, Some(&vec![1; rows.len()]), None).unwrap();
+        column.close().unwrap();
+
+        let labels: Vec<i64> = rows.iter().map(|&(_, label)| label).collect();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column.typed::<Int64Type>().write_batch(&labels, None, None).unwrap();
+        column.close().unwrap();
+
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        (dir, path)
+    }
+
+    // Three 4x4 images whose pixels encode their index, labelled 7, 0 and 9.
+    fn images() -> Vec<MnistImage> {
+        [7, 0, 9]
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| MnistImage {
+                width: 4,
+                height: 4,
+                pixels: (0..16).map(|p| (p * 17 + i * 5) as u8).collect(),
+                label,
+            })
+            .collect()
+    }
+
+    fn fixture(images: &[MnistImage]) -> (tempfile::TempDir, PathBuf) {
+        let rows: Vec<_> = images
+            .iter()
+            .map(|image| {
+                let png = encode_png(image.width as u32, image.height as u32, png::ColorType::Grayscale, &image.pixels);
+                (png, image.label as i64)
+            })
+            .collect();
+
+        write_fixture(&rows)
+    }
+
+    #[test]
+    fn loads_images_and_labels() {
+        let (_dir, path) = fixture(&images());
+        assert_eq!(load(&path).unwrap(), images());
+    }
+
+    #[test]
+    fn rejects_bad_rows() {
+        let gray = encode_png(2, 2, png::ColorType::Grayscale, &[0, 1, 2, 3]);
+        let (_dir, path) = write_fixture(&[(gray, 10)]);
+        assert!(load(&path).unwrap_err().to_string().contains("label 10 is not a digit"));
+
+        let rgb = encode_png(1, 1, png::ColorType::Rgb, &[0, 1, 2]);
+        let (_dir, path) = write_fixture(&[(rgb, 3)]);
+        assert!(load(&path).unwrap_err().to_string().contains("expected an 8-bit grayscale image"));
+
+        let (_dir, path) = write_fixture(&[(b"not a png".to_vec(), 3)]);
+        assert!(load(&path).is_err());
+
+        assert!(load(Path::new("/nonexistent/mnist.parquet")).is_err());
+    }
+
+    #[test]
+    fn loads_an_empty_file() {
+        let (_dir, path) = write_fixture(&[]);
+        assert_eq!(load(&path).unwrap(), []);
+    }
+
+    #[test]
+    fn normalizes_into_rows() {
+        let image = MnistImage { width: 2, height: 2, pixels: vec![0, 51, 255, 128], label: 3 };
+        let rows = to_rows(&[image]);
+        assert_eq!(rows.len(), 1);
+
+        let values: Vec<Fixed> = rows[0].iter().copied().map(Fixed::from_raw).collect();
+        assert_eq!(values.len(), 4 + CLASSES);
+        assert_eq!(values[0], Fixed::ZERO);
+        assert_eq!(values[1], Fixed::from_f32(0.2));
+        assert_eq!(values[2], Fixed::ONE);
+        assert_eq!(values[3], Fixed::from_f32(128.0 / 255.0));
+
+        let mut label = [Fixed::ZERO; CLASSES];
+        label[3] = Fixed::ONE;
+        assert_eq!(values[4..], label);
+    }
+
+    #[test]
+    fn subsamples_deterministically() {
+        let all = images();
+        let picked = subsample(all.clone(), 2, 42);
+        assert_eq!(picked.len(), 2);
+        assert_ne!(picked[0], picked[1]);
+        assert!(picked.iter().all(|image| all.contains(image)));
+        assert_eq!(subsample(all.clone(), 2, 42), picked);
+
+        // Asking for more images than there are returns all of them, shuffled.
+        let mut shuffled = subsample(all.clone(), 10, 7);
+        assert_eq!(shuffled.len(), 3);
+        shuffled.sort_by_key(|image| image.pixels[0]);
+        assert_eq!(shuffled, all);
+
+        assert_eq!(subsample(all, 0, 1), []);
+    }
+
+    #[test]
+    fn downscales_by_averaging_blocks() {
+        let image = MnistImage { width: 4, height: 4, pixels: (0..16).collect(), label: 5 };
+        let small = image.downscale(2);
+        // The top-left block holds 0, 1, 4 and 5, whose mean 2.5 rounds to 3.
+        assert_eq!(small, MnistImage { width: 2, height: 2, pixels: vec![3, 5, 11, 13], label: 5 });
+        assert_eq!(image.downscale(1), image);
+
+        // Blocks at the edges of a 5x5 image are smaller: the top-right one holds 40 and 90.
+        let image = MnistImage { width: 5, height: 5, pixels: (0..25).map(|p| p * 10).collect(), label: 1 };
+        let small = image.downscale(2);
+        assert_eq!((small.width, small.height), (3, 3));
+        assert_eq!(small.pixels, vec![30, 50, 65, 130, 150, 165, 205, 225, 240]);
+    }
+
+    #[test]
+    fn feeds_a_training_run() {
+        let (_dir, path) = fixture(&images());
+        let images: Vec<_> = subsample(load(&path).unwrap(), 2, 1).iter().map(|image| image.downscale(2)).collect();
+        let rows = to_rows(&images);
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row.len() == 4 + CLASSES));
+    }
+}
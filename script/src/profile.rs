@@ -0,0 +1,136 @@
+//! A cost model of the training guest, fitted to cycle counts measured with the executor.
+//!
+//! Every epoch runs a forward and a backward pass over every example, each touching every
+//! parameter, so training costs about the same number of cycles per epoch, example and parameter.
+//! Reading the examples and the checkpoint, hashing them and evaluating the trained model happen
+//! once per run and cost about the same per example and parameter. The model is therefore
+//!
+//! ```text
+//! cycles = fixed + per_step * epochs * examples * params + per_example * examples * params
+//! ```
+//!
+//! fitted by least squares. The prover splits an execution into shards of a fixed number of cycles
+//! and proves them one after the other, so its memory is set by the shard size and its time grows
+//! with the number of shards.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// The default shard size of the SP1 prover, in cycles.
+pub const DEFAULT_SHARD_SIZE: u64 = 1 << 22;
+
+/// The size of a training run, as far as its cost is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Workload {
+    pub params: u64,
+    pub epochs: u64,
+    pub examples: u64,
+}
+
+impl Workload {
+    // The terms the cost model weighs: a constant, the training steps and the per-example work.
+    fn features(&self) -> [f64; 3] {
+        let example_params = (self.examples * self.params) as f64;
+        [1.0, self.epochs as f64 * example_params, example_params]
+    }
+}
+
+/// The cycles of a training run as a linear function of its [`Workload`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CostModel {
+    /// The cycles of a run that does no work.
+    pub fixed: f64,
+    /// The cycles per epoch, example and parameter.
+    pub per_step: f64,
+    /// The cycles per example and parameter that do not depend on the epochs.
+    pub per_example: f64,
+    /// The fraction of the variance of the measured cycles the model explains.
+    pub r_squared: f64,
+}
+
+impl CostModel {
+    /// Fit the model to measured `(workload, cycles)` pairs by least squares.
+    ///
+    /// The workloads must vary the epochs and the number of examples times parameters
+    /// independently, otherwise the two costs cannot be told apart.
+    pub fn fit(samples: &[(Workload, u64)]) -> Result<Self> {
+        // Scale every feature to at most one, so that the normal equations stay well conditioned.
+        let mut scale = [0f64; 3];
+        for (workload, _) in samples {
+            for (s, f) in scale.iter_mut().zip(workload.features()) {
+                *s = s.max(f);
+            }
+        }
+        if scale.contains(&0.0) {
+            bail!("the sweep has no work to fit");
+        }
+
+        // The normal equations `X^T X c = X^T y`, as an augmented matrix.
+        let mut system = [[0f64; 4]; 3];
+        for (workload, cycles) in samples {
+            let x = workload.features();
+            for i in 0..3 {
+                for j in 0..3 {
+                    system[i][j] += x[i] / scale[i] * x[j] / scale[j];
+                }
+                system[i][3] += x[i] / scale[i] * *cycles as f64;
+            }
+        }
+
+        let Some(scaled) = solve(system) else {
+            bail!("the sweep must vary the epochs independently of the examples and parameters");
+        };
+        let [fixed, per_step, per_example] = [0, 1, 2].map(|i| scaled[i] / scale[i]);
+        let mut model = Self { fixed, per_step, per_example, r_squared: 1.0 };
+
+        let mean = samples.iter().map(|(_, cycles)| *cycles as f64).sum::<f64>() / samples.len() as f64;
+        let (mut residual, mut total) = (0f64, 0f64);
+        for (workload, cycles) in samples {
+            let cycles = *cycles as f64;
+            residual += (cycles - model.predict(workload)).powi(2);
+            total += (cycles - mean).powi(2);
+        }
+        if total > 0.0 {
+            model.r_squared = 1.0 - residual / total;
+        }
+
+        Ok(model)
+    }
+
+    /// The predicted cycles of a run, never less than zero.
+    pub fn predict(&self, workload: &Workload) -> f64 {
+        let [one, steps, example_params] = workload.features();
+        (self.fixed * one + self.per_step * steps + self.per_example * example_params).max(0.0)
+    }
+}
+
+/// The number of shards of `shard_size` cycles that `cycles` are split into.
+pub fn shards(cycles: u64, shard_size: u64) -> u64 {
+    cycles.div_ceil(shard_size).max(1)
+}
+
+// Solve a 3x3 linear system given as an augmented matrix, by Gaussian elimination with partial
+// pivoting. Returns `None` if the system is singular.
+fn solve(mut m: [[f64; 4]; 3]) -> Option<[f64; 3]> {
+    for col in 0..3 {
+        let pivot = (col..3).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(col, pivot);
+
+        for row in col + 1..3 {
+            let factor = m[row][col] / m[col][col];
+            for k in col..4 {
+                m[row][k] -= factor * m[col][k];
+            }
+        }
+    }
+
+    let mut x = [0f64; 3];
+    for row in (0..3).rev() {
+        let known: f64 = (row + 1..3).map(|k| m[row][k] * x[k]).sum();
+        x[row] = (m[row][3] - known) / m[row][row];
+    }
+    Some(x)
+}
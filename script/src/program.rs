@@ -0,0 +1,150 @@
+//! The registry of guest programs. Each entry has its ELF, as the build script built it, the kinds
+//! of public values it commits and the order in which it reads its inputs, so the binaries run,
+//! prove and verify every guest the same way.
+
+use anyhow::{bail, ensure, Result};
+use clap::ValueEnum;
+use fibonacci_lib::{Checkpoint, Commitment, GuestMode};
+use serde::{Deserialize, Serialize};
+use sp1_sdk::{HashableKey, SP1Proof, SP1ProofWithPublicValues, SP1Stdin, SP1VerifyingKey};
+
+use crate::claim::{Claim, ClaimKind};
+use crate::training::TrainingRun;
+
+/// A guest program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Program {
+    /// Trains a two-input perceptron.
+    Perceptron,
+    /// Trains a `Sequential` dense network or the two-layer `MLP`.
+    Mlp,
+    /// Runs the model of a checkpoint on one input.
+    Infer,
+    /// Aggregates the segment proofs of a training run.
+    Aggregate,
+}
+
+/// The inputs of a program.
+pub enum Inputs<'a> {
+    /// A training run, and how to commit to its weights.
+    Training(&'a TrainingRun, Commitment),
+    /// A checkpoint, and the raw input to run its model on.
+    Inference(&'a Checkpoint, &'a [u32]),
+    /// The compressed proofs of consecutive segments of a training run, and the verification key
+    /// of the program that proved them.
+    Aggregation(&'a SP1VerifyingKey, Vec<SP1ProofWithPublicValues>),
+}
+
+impl Program {
+    pub const ALL: [Program; 4] = [Program::Perceptron, Program::Mlp, Program::Infer, Program::Aggregate];
+
+    /// The program that runs `mode`.
+    pub fn for_mode(mode: GuestMode) -> Self {
+        match mode {
+            GuestMode::Perceptron => Program::Perceptron,
+            GuestMode::Sequential | GuestMode::Mlp => Program::Mlp,
+            GuestMode::Infer => Program::Infer,
+        }
+    }
+
+    /// The name of the program, as `--program` takes it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Program::Perceptron => "perceptron",
+            Program::Mlp => "mlp",
+            Program::Infer => "infer",
+            Program::Aggregate => "aggregate",
+        }
+    }
+
+    /// The ELF of the program.
+    pub fn elf(self) -> &'static [u8] {
+        match self {
+            Program::Perceptron => include_bytes!(env!("PERCEPTRON_ELF_PATH")),
+            Program::Mlp => include_bytes!(env!("MLP_ELF_PATH")),
+            Program::Infer => include_bytes!(env!("INFER_ELF_PATH")),
+            Program::Aggregate => include_bytes!(env!("AGGREGATION_ELF_PATH")),
+        }
+    }
+
+    /// Where the build script built the ELF.
+    pub fn elf_path(self) -> &'static str {
+        match self {
+            Program::Perceptron => env!("PERCEPTRON_ELF_PATH"),
+            Program::Mlp => env!("MLP_ELF_PATH"),
+            Program::Infer => env!("INFER_ELF_PATH"),
+            Program::Aggregate => env!("AGGREGATION_ELF_PATH"),
+        }
+    }
+
+    /// The SHA-256 digest of the ELF in hex, as the build script computed it.
+    pub fn elf_sha256(self) -> &'static str {
+        match self {
+            Program::Perceptron => env!("PERCEPTRON_ELF_SHA256"),
+            Program::Mlp => env!("MLP_ELF_SHA256"),
+            Program::Infer => env!("INFER_ELF_SHA256"),
+            Program::Aggregate => env!("AGGREGATION_ELF_SHA256"),
+        }
+    }
+
+    /// The guest modes the program runs, none for the aggregation program.
+    pub fn modes(self) -> &'static [GuestMode] {
+        match self {
+            Program::Perceptron => &[GuestMode::Perceptron],
+            Program::Mlp => &[GuestMode::Sequential, GuestMode::Mlp],
+            Program::Infer => &[GuestMode::Infer],
+            Program::Aggregate => &[],
+        }
+    }
+
+    /// The kinds of public values the program commits.
+    pub fn claims(self) -> &'static [ClaimKind] {
+        match self {
+            Program::Perceptron => &[ClaimKind::Perceptron, ClaimKind::Digest],
+            Program::Mlp => &[ClaimKind::Sequential, ClaimKind::Mlp, ClaimKind::Digest],
+            Program::Infer => &[ClaimKind::Inference],
+            Program::Aggregate => &[ClaimKind::Aggregate],
+        }
+    }
+
+    /// Decode public values of the program as `kind`.
+    pub fn decode(self, kind: ClaimKind, bytes: &[u8]) -> Result<Claim> {
+        ensure!(self.claims().contains(&kind), "the {} program does not commit {:?} claims", self.name(), kind);
+        Claim::decode(kind, bytes)
+    }
+
+    /// Write the inputs of the program to stdin, in the order the guest reads them.
+    pub fn stdin(self, inputs: Inputs) -> Result<SP1Stdin> {
+        let stdin = match (self, inputs) {
+            (Program::Perceptron | Program::Mlp, Inputs::Training(run, commitment)) => {
+                ensure!(self.modes().contains(&run.mode), "the {} program does not train {:?}", self.name(), run.mode);
+                run.stdin(commitment)
+            }
+            (Program::Infer, Inputs::Inference(checkpoint, input)) => {
+                // Inference always commits digests of the weights and the input.
+                let mut stdin = SP1Stdin::new();
+                stdin.write(&GuestMode::Infer.to_u32());
+                stdin.write(&Commitment::Digest.to_u32());
+                stdin.write_vec(checkpoint.to_bytes());
+                stdin.write(&input);
+                stdin
+            }
+            (Program::Aggregate, Inputs::Aggregation(vk, proofs)) => {
+                // The proofs are written in the same order as their public values.
+                let public_values: Vec<Vec<u8>> = proofs.iter().map(|proof| proof.public_values.to_vec()).collect();
+                let mut stdin = SP1Stdin::new();
+                stdin.write(&vk.hash_u32());
+                stdin.write(&public_values);
+                for proof in proofs {
+                    let SP1Proof::Compressed(proof) = proof.proof else { bail!("segment proofs must be compressed") };
+                    stdin.write_proof(*proof, vk.vk.clone());
+                }
+                stdin
+            }
+            (program, _) => bail!("these are not inputs of the {} program", program.name()),
+        };
+
+        Ok(stdin)
+    }
+}
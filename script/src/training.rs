@@ -0,0 +1,97 @@
+//! The inputs of a training run, written to `SP1Stdin` in the order the guest reads them.
+
+use fibonacci_lib::{
+    evaluate_mlp_on, evaluate_perceptron_on, evaluate_sequential_on, update_checkpoint, Checkpoint, Commitment,
+    EvaluationStruct, Fixed, GuestMode, MerkleTree, TrainingState, EXTERNAL_DATASET, MLP,
+};
+use sp1_sdk::SP1Stdin;
+
+/// Everything the guest reads to train a model, apart from how it commits to the weights.
+#[derive(Debug, Clone)]
+pub struct TrainingRun {
+    /// The guest mode that trains the model.
+    pub mode: GuestMode,
+    /// The layer specs of a `Sequential` model, the shape of an `MLP`, or nothing.
+    pub architecture: Vec<u32>,
+    /// The dataset id, `EXTERNAL_DATASET` if `rows` are written to stdin.
+    pub d: u32,
+    pub rows: Vec<Vec<u32>>,
+    pub t: u32,
+    pub r: u32,
+    pub params: Vec<u32>,
+    /// The loss and optimizer to train with, and where a resumed run left the optimizer.
+    pub training: TrainingState<Fixed>,
+    /// The dataset id and examples of a held-out set to evaluate on, instead of the training set.
+    pub held_out: Option<(u32, Vec<Vec<u32>>)>,
+}
+
+impl TrainingRun {
+    pub fn stdin(&self, commitment: Commitment) -> SP1Stdin {
+        let mut stdin = SP1Stdin::new();
+        stdin.write(&self.mode.to_u32());
+        stdin.write(&commitment.to_u32());
+
+        stdin.write_vec(self.checkpoint().to_bytes());
+        self.write_dataset(&mut stdin);
+        stdin.write(&self.t);
+        stdin.write(&self.r);
+        self.write_held_out(&mut stdin);
+
+        stdin
+    }
+
+    /// The checkpoint of the initial parameters and optimizer state.
+    pub fn checkpoint(&self) -> Checkpoint {
+        assert!(self.mode != GuestMode::Infer, "inference is not a training run");
+        Checkpoint::new(self.mode, self.architecture.clone(), self.params.clone()).with_training(&self.training)
+    }
+
+    /// Train natively on the host and return the checkpoint of the updated parameters and
+    /// optimizer state.
+    pub fn train_native(&self) -> Checkpoint {
+        update_checkpoint(&self.checkpoint(), &self.rows, self.t, self.r)
+    }
+
+    /// The run that resumes training from where this one stops.
+    pub fn resume(&self, trained: &Checkpoint) -> TrainingRun {
+        TrainingRun { params: trained.params().to_vec(), training: trained.training(), ..self.clone() }
+    }
+
+    /// Evaluate the given raw parameters natively, as the guest evaluates the trained ones.
+    pub fn evaluate_native(&self, params: &[u32]) -> EvaluationStruct {
+        let rows = self.held_out.as_ref().map_or(&self.rows, |(_, rows)| rows);
+        let loss = self.training.loss;
+        let metrics = match self.mode {
+            GuestMode::Perceptron => {
+                let [w_l, w_r, b] = params[..] else { panic!("a perceptron has two weights and a bias") };
+                evaluate_perceptron_on(rows, w_l, w_r, b)
+            }
+            GuestMode::Sequential => evaluate_sequential_on(&self.architecture, rows, params, loss),
+            GuestMode::Mlp => evaluate_mlp_on(&MLP::from_params(self.shape(), params), rows, loss),
+            GuestMode::Infer => panic!("inference is not a training run"),
+        };
+
+        EvaluationStruct::new(MerkleTree::new(rows).root(), loss, metrics)
+    }
+
+    fn shape(&self) -> [u32; 3] {
+        self.architecture[..].try_into().expect("MLP shape has three widths")
+    }
+
+    /// Write the dataset id, followed by the examples themselves if the dataset is external.
+    fn write_dataset(&self, stdin: &mut SP1Stdin) {
+        stdin.write(&self.d);
+        if self.d == EXTERNAL_DATASET {
+            stdin.write(&self.rows);
+        }
+    }
+
+    /// Write the id of the held-out set, if any, followed by its examples if it is external.
+    fn write_held_out(&self, stdin: &mut SP1Stdin) {
+        let d = self.held_out.as_ref().map(|(d, _)| *d);
+        stdin.write(&d);
+        if let Some((EXTERNAL_DATASET, rows)) = &self.held_out {
+            stdin.write(rows);
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! A differential test of the guests against the lib. Each run trains a randomized configuration,
+//! loss and optimizer included, in the SP1 executor and checks that the committed values are
+//! bit-identical to the native library call on the same inputs.
+//!
+//! Every built-in dataset, examples read from stdin and held-out sets are covered, with the
+//! perceptron, the MLP and `Sequential` models, and both commitments. The runs execute the embedded
+//! guests, so the tests are ignored by default:
+//! ```shell
+//! cargo test --release --test difftest -- --ignored
+//! ```
+//! `DIFFTEST_RUNS` sets the number of seeds of each configuration and `DIFFTEST_SEED` the first
+//! one. A failing assertion names its seed, so it can be reproduced with
+//! `DIFFTEST_SEED=<seed> DIFFTEST_RUNS=1`.
+
+use alloy_sol_types::SolType;
+use fibonacci_lib::optim::{Adam, AnyOptimizer, Momentum, Sgd};
+use fibonacci_lib::{
+    ActivationKind, Commitment, DatasetId, DigestPublicValuesStruct, EvaluationStruct, Fixed, GuestMode, LayerSpec,
+    LossKind, MerkleTree, MlpPublicValuesStruct, OptimizerStruct, PublicValuesStruct, Scalar, Schedule, Sequential,
+    SequentialPublicValuesStruct, TrainingState, EXTERNAL_DATASET,
+};
+use fibonacci_script::elf;
+use fibonacci_script::program::{Inputs, Program};
+use fibonacci_script::training::TrainingRun;
+use sp1_sdk::ProverClient;
+use std::ops::Range;
+
+const COMMITMENTS: [Commitment; 2] = [Commitment::Clear, Commitment::Digest];
+
+/// The models under test.
+#[derive(Copy, Clone, Debug)]
+enum Model {
+    Perceptron,
+    Mlp,
+    Sequential,
+}
+
+impl Model {
+    const ALL: [Model; 3] = [Model::Perceptron, Model::Mlp, Model::Sequential];
+
+    /// Whether the model trains on examples of `width` values, the last `targets` of them targets.
+    fn fits(self, width: usize, targets: usize) -> bool {
+        match self {
+            Model::Perceptron => (width, targets) == (3, 1),
+            Model::Mlp | Model::Sequential => width > targets,
+        }
+    }
+}
+
+#[test]
+#[ignore = "executes the embedded guests"]
+fn built_in_datasets() {
+    let client = client();
+    for seed in seeds() {
+        for dataset in DatasetId::ALL {
+            let (d, rows, targets) = (dataset.to_u32(), dataset.rows(), dataset.targets());
+            for_each_case(&client, seed, dataset.name(), &Data { d, rows, targets, held_out: None });
+        }
+    }
+}
+
+#[test]
+#[ignore = "executes the embedded guests"]
+fn external_dataset() {
+    let client = client();
+    for seed in seeds() {
+        let mut rng = Rng::new(seed);
+        for (width, targets) in [(3, 1), (7, 3), (2 + (rng.next() % 7) as usize, 1)] {
+            let rows = random_rows(&mut rng, width, targets);
+            let data = Data { d: EXTERNAL_DATASET, rows, targets, held_out: None };
+            for_each_case(&client, seed, &format!("external {}:{}", width, targets), &data);
+        }
+    }
+}
+
+#[test]
+#[ignore = "executes the embedded guests"]
+fn held_out_evaluation() {
+    let client = client();
+    for seed in seeds() {
+        let mut rng = Rng::new(seed);
+        for dataset in DatasetId::ALL {
+            let (width, targets) = (dataset.width(), dataset.targets());
+
+            // Evaluate on examples from stdin, and on a built-in dataset of the same width.
+            let external = (EXTERNAL_DATASET, random_rows(&mut rng, width, targets));
+            let data = Data { d: dataset.to_u32(), rows: dataset.rows(), targets, held_out: Some(external) };
+            for_each_case(&client, seed, &format!("{} held out on external", dataset.name()), &data);
+
+            let others: Vec<DatasetId> = DatasetId::ALL.into_iter().filter(|d| d.width() == width).collect();
+            let other = others[(rng.next() % others.len() as u64) as usize];
+            let rows = random_rows(&mut rng, width, targets);
+            let data = Data { d: EXTERNAL_DATASET, rows, targets, held_out: Some((other.to_u32(), other.rows())) };
+            for_each_case(&client, seed, &format!("external held out on {}", other.name()), &data);
+        }
+    }
+}
+
+/// The examples a run trains on, and the held-out set it is evaluated on.
+struct Data {
+    d: u32,
+    rows: Vec<Vec<u32>>,
+    targets: usize,
+    held_out: Option<(u32, Vec<Vec<u32>>)>,
+}
+
+/// Check every model that fits `data`, with both commitments.
+fn for_each_case(client: &ProverClient, seed: u64, name: &str, data: &Data) {
+    let width = data.rows[0].len();
+    for model in Model::ALL.into_iter().filter(|model| model.fits(width, data.targets)) {
+        for commitment in COMMITMENTS {
+            let run = training_run(&mut Rng::new(seed), model, data);
+            let context = format!("seed {}: {:?} on {} with {:?} commitment", seed, model, name, commitment);
+            check(client, &run, commitment, &context);
+            println!("{} ok", context);
+        }
+    }
+}
+
+/// A run of `model` on `data`, with random epochs, learning rate, weights and training state.
+fn training_run(rng: &mut Rng, model: Model, data: &Data) -> TrainingRun {
+    let inputs = (data.rows[0].len() - data.targets) as u32;
+    let outputs = data.targets as u32;
+    let hidden = 1 + (rng.next() % 8) as u32;
+
+    let (mode, architecture, specs) = match model {
+        Model::Perceptron => (GuestMode::Perceptron, vec![], vec![]),
+        Model::Mlp => {
+            let specs = vec![
+                LayerSpec::new(inputs, hidden, ActivationKind::Relu),
+                LayerSpec::new(hidden, outputs, ActivationKind::Identity),
+            ];
+            (GuestMode::Mlp, vec![inputs, hidden, outputs], specs)
+        }
+        Model::Sequential => {
+            // Up to three layers, each with a random activation.
+            let depth = 1 + (rng.next() % 3) as usize;
+            let mut widths = vec![inputs];
+            widths.extend((1..depth).map(|_| 1 + (rng.next() % 8) as u32));
+            widths.push(outputs);
+            let specs: Vec<LayerSpec> = widths
+                .windows(2)
+                .map(|w| LayerSpec::new(w[0], w[1], ActivationKind::from_u32((rng.next() % 5) as u32).unwrap()))
+                .collect();
+            (GuestMode::Sequential, LayerSpec::encode_all(&specs), specs)
+        }
+    };
+
+    let (params, loss, t, r): (Vec<u32>, _, _, _) = match model {
+        Model::Perceptron => {
+            let params = (0..3).map(|_| Fixed::from_f32(rng.uniform(-2.0, 2.0)).to_raw()).collect();
+            (params, LossKind::Mse, 1 + (rng.next() % 20) as u32, rng.uniform(0.001, 1.0))
+        }
+        Model::Mlp | Model::Sequential => {
+            let params =
+                Sequential::<Fixed>::seeded(&specs, rng.next()).params().into_iter().map(Fixed::to_raw).collect();
+            let loss = LossKind::from_u32((rng.next() % 3) as u32).expect("three losses");
+            (params, loss, 1 + (rng.next() % 5) as u32, rng.uniform(0.001, 0.1))
+        }
+    };
+    let training = training_state(rng, params.len(), loss);
+
+    TrainingRun {
+        mode,
+        architecture,
+        d: data.d,
+        rows: data.rows.clone(),
+        t,
+        r: Fixed::from_f32(r).to_raw(),
+        params,
+        training,
+        held_out: data.held_out.clone(),
+    }
+}
+
+/// A random optimizer, schedule and batch size for `loss`, resumed from a random epoch.
+fn training_state(rng: &mut Rng, num_params: usize, loss: LossKind) -> TrainingState<Fixed> {
+    let fixed = Fixed::from_f32;
+    let optimizer = match rng.next() % 3 {
+        0 => AnyOptimizer::Sgd(Sgd),
+        1 => AnyOptimizer::Momentum(Momentum::new(fixed(rng.uniform(0.5, 0.95)), num_params)),
+        _ => AnyOptimizer::Adam(Adam::new(fixed(0.9), fixed(0.999), fixed(1e-2), num_params)),
+    };
+    let schedule = match rng.next() % 3 {
+        0 => Schedule::Constant,
+        1 => Schedule::StepDecay { every: 1 + (rng.next() % 5) as u32, factor: fixed(rng.uniform(0.1, 0.9)) },
+        _ => Schedule::Cosine { epochs: 1 + (rng.next() % 20) as u32, min: fixed(rng.uniform(0.0, 0.5)) },
+    };
+
+    let (batch_size, epoch) = (1 + (rng.next() % 4) as u32, (rng.next() % 10) as u32);
+
+    TrainingState { loss, optimizer, schedule, batch_size, epoch }
+}
+
+/// Up to eight examples with random inputs and `targets` targets of zero or one.
+fn random_rows(rng: &mut Rng, width: usize, targets: usize) -> Vec<Vec<u32>> {
+    let count = 1 + (rng.next() % 8) as usize;
+    (0..count)
+        .map(|_| {
+            let inputs = (targets..width).map(|_| rng.uniform(-4.0, 4.0)).collect::<Vec<_>>();
+            let labels = (0..targets).map(|_| (rng.next() % 2) as f32).collect::<Vec<_>>();
+            inputs.into_iter().chain(labels).map(|x| Fixed::from_f32(x).to_raw()).collect()
+        })
+        .collect()
+}
+
+/// Execute `run` and compare the committed values with the native library call.
+fn check(client: &ProverClient, run: &TrainingRun, commitment: Commitment, context: &str) {
+    let program = Program::for_mode(run.mode);
+    let stdin = program.stdin(Inputs::Training(run, commitment)).expect("inputs of the program");
+    let (output, _) =
+        client.execute(program.elf(), stdin).run().unwrap_or_else(|e| panic!("{}: execution failed: {}", context, e));
+    let output = output.as_slice();
+
+    let trained = run.train_native();
+    let root = MerkleTree::new(&run.rows).root();
+
+    match (commitment, run.mode) {
+        (Commitment::Digest, _) => {
+            let decoded = decode::<DigestPublicValuesStruct>(output, context);
+            assert_eq!((decoded.mode, decoded.t, decoded.r), (run.mode.to_u32(), run.t, run.r), "{}", context);
+            assert_eq!(decoded.dataset_root, root, "{}", context);
+            assert_eq!(decoded.weights_digest, run.checkpoint().digest(), "{}", context);
+            assert_eq!(decoded.updated_weights_digest, trained.digest(), "{}", context);
+            assert_eq!(decoded.evaluation, run.evaluate_native(trained.params()), "{}", context);
+        }
+        (Commitment::Clear, GuestMode::Perceptron) => {
+            let decoded = decode::<PublicValuesStruct>(output, context);
+            let inputs = (decoded.d, decoded.t, decoded.r, vec![decoded.w_l, decoded.w_r, decoded.b]);
+            assert_eq!(inputs, (run.d, run.t, run.r, run.params.clone()), "{}", context);
+            assert_eq!(decoded.dataset_root, root, "{}", context);
+            let updated = [decoded.updated_w_l, decoded.updated_w_r, decoded.updated_b];
+            check_trained(run, &updated, &decoded.optimizer, &decoded.evaluation, context);
+        }
+        (Commitment::Clear, GuestMode::Mlp) => {
+            let decoded = decode::<MlpPublicValuesStruct>(output, context);
+            let inputs = (decoded.shape, decoded.d, decoded.t, decoded.r, decoded.params);
+            assert_eq!(inputs, (run.architecture.clone(), run.d, run.t, run.r, run.params.clone()), "{}", context);
+            assert_eq!(decoded.dataset_root, root, "{}", context);
+            check_trained(run, &decoded.updated_params, &decoded.optimizer, &decoded.evaluation, context);
+        }
+        (Commitment::Clear, GuestMode::Sequential) => {
+            let decoded = decode::<SequentialPublicValuesStruct>(output, context);
+            let inputs = (decoded.layers, decoded.d, decoded.t, decoded.r, decoded.params);
+            assert_eq!(inputs, (run.architecture.clone(), run.d, run.t, run.r, run.params.clone()), "{}", context);
+            assert_eq!(decoded.dataset_root, root, "{}", context);
+            check_trained(run, &decoded.updated_params, &decoded.optimizer, &decoded.evaluation, context);
+        }
+        (_, GuestMode::Infer) => unreachable!("inference is not a training run"),
+    }
+}
+
+/// Decode the public values of `T`, naming the failing run if they are malformed.
+fn decode<T: SolType>(output: &[u8], context: &str) -> T::RustType {
+    T::abi_decode(output, true).unwrap_or_else(|e| panic!("{}: undecodable public values: {}", context, e))
+}
+
+/// Check the trained weights, the optimizer and the evaluation committed in the clear.
+fn check_trained(
+    run: &TrainingRun,
+    updated_params: &[u32],
+    optimizer: &OptimizerStruct,
+    evaluation: &EvaluationStruct,
+    context: &str,
+) {
+    let trained = run.train_native();
+    assert_eq!(updated_params, trained.params(), "{}", context);
+    assert_eq!(*optimizer, OptimizerStruct::new(&run.training, &trained.training()), "{}", context);
+    assert_eq!(*evaluation, run.evaluate_native(updated_params), "{}", context);
+}
+
+/// The prover client, once the embedded guests are checked to be the ones of this build.
+fn client() -> ProverClient {
+    elf::check_embedded().expect("embedded guests");
+    ProverClient::new()
+}
+
+/// The seeds to run, from `DIFFTEST_SEED` and `DIFFTEST_RUNS`.
+fn seeds() -> Range<u64> {
+    let var = |name: &str, default: u64| {
+        let value = std::env::var(name).unwrap_or_else(|_| default.to_string());
+        value.parse().unwrap_or_else(|_| panic!("{} is not a number: {}", name, value))
+    };
+    let seed = var("DIFFTEST_SEED", 1);
+    seed..seed + var("DIFFTEST_RUNS", 2)
+}
+
+/// An xorshift generator, so that every run is reproducible from its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Scramble the seed so that consecutive seeds give unrelated runs.
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A value in `[low, high)`.
+    fn uniform(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}